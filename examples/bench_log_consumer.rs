@@ -1,5 +1,6 @@
 extern crate rshm;
 
+#[allow(dead_code)]
 mod log;
 
 use self::log::LogConsumer;
@@ -7,11 +8,11 @@ use self::log::LogConsumer;
 use std::{
     mem::size_of,
     num::NonZero,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use clap::{self, Parser};
-use rshm::shm::ShmDefinition;
+use rshm::shm::{ShmDefinition, ShmSafe, HEADER_SIZE};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -23,12 +24,17 @@ struct Args {
     /// Number of events to produce
     #[clap(short, long, value_parser, default_value_t = 100000)]
     count: usize,
+
+    /// Locks the log in memory and faults in its pages before the first event
+    #[clap(short, long, value_parser, default_value_t = false)]
+    lock: bool,
 }
 
 ///
 /// Reads a given number of records from the shared memory log.
 ///
-/// Warmup records are part of the count.
+/// Warmup records are part of the count. The consumer waits for the producer to create
+/// the log, so either side can be started first.
 ///
 /// It will log information about the record consumption"
 fn main() {
@@ -36,42 +42,44 @@ fn main() {
 
     let args = Args::parse();
 
-    test_light_load(args.warmup_count, args.count);
+    test_light_load(args.warmup_count, args.count, args.lock);
 }
 
-fn test_light_load(warmup_count: usize, count: usize) {
-    let definition = ShmDefinition {
-        path: "test_log".to_string(),
-        size: NonZero::new(size_of::<LigthRecord>() * (warmup_count + count)).unwrap(),
-    };
-    let log_shm = definition.open().unwrap();
+fn test_light_load(warmup_count: usize, count: usize, lock: bool) {
+    let definition = ShmDefinition::new(
+        "test_log",
+        NonZero::new(HEADER_SIZE + size_of::<LigthRecord>() * (warmup_count + count)).unwrap(),
+    )
+    .with_header()
+    .with_layout::<LigthRecord>();
+    let log_shm = definition
+        .open_read_only_wait(Duration::from_secs(60))
+        .unwrap();
+    if lock {
+        log_shm.lock().unwrap();
+        log_shm.prefault();
+    }
     let mut log: LogConsumer<LigthRecord> = LogConsumer::new(log_shm);
 
     let mut sequence = 0;
 
     // Warmup
     while sequence < warmup_count {
-        match log.next() {
-            Some(t) => {
-                sequence = t.value.0;
-            }
-            None => {}
+        if let Some(t) = log.next() {
+            sequence = t.value.0;
         }
     }
 
     let mut result = Vec::with_capacity(count);
     while sequence < count {
-        match log.next() {
-            Some(t) => {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_nanos();
-
-                result.push((t.value.0, now, t.value.1));
-                sequence = t.value.0;
-            }
-            None => {}
+        if let Some(t) = log.next() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+
+            result.push((t.value.0, now, t.value.1));
+            sequence = t.value.0;
         }
     }
 
@@ -95,3 +103,6 @@ fn test_light_load(warmup_count: usize, count: usize) {
 pub struct LigthRecord {
     pub value: (usize, u128),
 }
+
+// Records only hold integers.
+unsafe impl ShmSafe for LigthRecord {}
@@ -1,5 +1,6 @@
 extern crate rshm;
 
+#[allow(dead_code)]
 mod log;
 use self::log::LogProducer;
 use core::ops::Add;
@@ -11,7 +12,7 @@ use std::{
 };
 
 use clap::{self, Parser};
-use rshm::shm::ShmDefinition;
+use rshm::shm::{ShmDefinition, ShmSafe, HEADER_SIZE};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -27,11 +28,15 @@ struct Args {
     /// Number of events to produce
     #[clap(short, long, value_parser, default_value_t = 100000)]
     count: usize,
+
+    /// Locks the log in memory and faults in its pages before the first event
+    #[clap(short, long, value_parser, default_value_t = false)]
+    lock: bool,
 }
 
 /// The production side of the benchmark test.
 ///
-/// It must be started before the consumption side.
+/// The consumption side waits for the log to be created and initialized by this side.
 ///
 /// It will produce a set number of records at the specified interval. Warmup records are part
 /// of the count used for the benchmark.
@@ -44,15 +49,24 @@ fn main() {
         args.warmup_count,
         args.count,
         std::time::Duration::from_micros(args.beat),
+        args.lock,
     );
 }
 
-fn run_light_load(warmup_count: usize, count: usize, beat: std::time::Duration) {
-    let log_definition = ShmDefinition {
-        path: "test_log".to_string(),
-        size: NonZero::new(size_of::<LigthRecord>() * (warmup_count + count)).unwrap(),
-    };
+fn run_light_load(warmup_count: usize, count: usize, beat: std::time::Duration, lock: bool) {
+    let log_definition = ShmDefinition::new(
+        "test_log",
+        NonZero::new(HEADER_SIZE + size_of::<LigthRecord>() * (warmup_count + count)).unwrap(),
+    )
+    .with_header()
+    .with_layout::<LigthRecord>();
     let log_shm = log_definition.create().unwrap();
+    if lock {
+        log_shm.lock().unwrap();
+        log_shm.prefault();
+    }
+    // The log needs no initialization: the shared memory is zero filled.
+    log_shm.mark_initialized().unwrap();
     let mut log: LogProducer<LigthRecord> = LogProducer::new(log_shm);
 
     std::thread::sleep(std::time::Duration::from_secs(30));
@@ -96,3 +110,6 @@ fn build_light_record(seq_num: usize) -> LigthRecord {
 pub struct LigthRecord {
     pub value: (usize, u128),
 }
+
+// Records only hold integers.
+unsafe impl ShmSafe for LigthRecord {}
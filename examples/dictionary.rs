@@ -1,107 +1,132 @@
+use std::collections::HashMap;
 use std::hash::Hash;
-use std::{collections::HashMap, mem::size_of};
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+use std::num::NonZero;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use nix::errno::Errno;
 use nix::Result;
-use rshm::shm::{OwnedShmMap, ShmMap};
+use rshm::shm::{OwnedShmMap, ReadOnlyShmMap, ShmSafe};
+
+// We keep the number of written records at the beginning
+const WRITTEN_RECORDS_OFFSET: usize = 0;
+
+// Ensure Alignment
+fn records_offset<R>() -> usize {
+    size_of::<AtomicUsize>().next_multiple_of(align_of::<R>())
+}
+
+fn record_offset<R>(i: usize) -> usize {
+    records_offset::<R>() + i * size_of::<R>()
+}
 
 /// The client of a shared memory dictionary.
 pub struct ShmDictionaryClient<K, R: Record<K>> {
-    _map: ShmMap,
-    written_records_ptr: *const usize,
-    end_ptr: *const R,
+    map: ReadOnlyShmMap,
     next_read: usize,
     index: HashMap<K, usize>,
+    _records: PhantomData<R>,
 }
 
 impl<K: Eq + Hash + Clone, R: Record<K>> ShmDictionaryClient<K, R> {
-    pub fn new(map: ShmMap) -> Self {
-        // We keep the number of written bytes of the beginning
-        let written_records_ptr = map.head() as *const usize;
-        // Ensure Alignment
-        let end_ptr = unsafe { (map.head() as *const R).add(1) };
+    pub fn new(map: ReadOnlyShmMap) -> Self {
         Self {
-            _map: map,
-            written_records_ptr: written_records_ptr,
-            end_ptr: end_ptr,
+            map,
             next_read: 0,
             index: HashMap::new(),
+            _records: PhantomData,
         }
     }
 
     pub fn get(&mut self, key: &K) -> Result<R> {
-        let records_count = unsafe { self.written_records_ptr.read_volatile() };
+        let records_count = self
+            .map
+            .view::<AtomicUsize>(WRITTEN_RECORDS_OFFSET)
+            .map_err(|_| Errno::EFAULT)?
+            .load(Ordering::Acquire);
+        // The owner grows the shared memory when it runs out of space for new records.
+        if record_offset::<R>(records_count) > self.map.len() {
+            self.map.refresh().map_err(|_| Errno::EIO)?;
+        }
         if !self.index.contains_key(key) {
-            while self.next_read < records_count {
-                let record = unsafe { self.end_ptr.read_volatile() };
-                self.index.insert(record.key().clone(), self.next_read);
-                self.next_read += 1;
-
-                unsafe {
-                    self.end_ptr = self.end_ptr.add(1);
-                }
+            let records = self
+                .map
+                .view_array::<R>(records_offset::<R>(), records_count)
+                .map_err(|_| Errno::EFAULT)?;
+            for (i, record) in records.iter().enumerate().skip(self.next_read) {
+                self.index.insert(record.key().clone(), i);
             }
+            self.next_read = records_count;
         };
-        self.index
-            .get(key)
-            .ok_or(Errno::ENOKEY)
-            .and_then(|i| unsafe { Ok(self.end_ptr.sub(records_count - i).read_volatile()) })
+        let i = self.index.get(key).ok_or(Errno::ENOKEY)?;
+        self.map
+            .view::<R>(record_offset::<R>(*i))
+            .copied()
+            .map_err(|_| Errno::EFAULT)
     }
 }
 
 /// The owner of a shared memory dictionary
 pub struct ShmDictionaryOwner<K, R: Record<K>> {
-    _map: OwnedShmMap,
-    written_records_ptr: *mut usize,
-    end_ptr: *mut R,
+    map: OwnedShmMap,
     available: usize,
     index: HashMap<K, usize>,
+    _records: PhantomData<R>,
 }
 
 impl<K: Eq + Hash, R: Record<K>> ShmDictionaryOwner<K, R> {
     pub fn new(map: OwnedShmMap) -> Self {
-        let size = map.definition.size;
-
-        // We keep the number of written bytes of the beginning
-        let written_records_ptr = map.head() as *mut usize;
-        // Ensure Alignment
-        let end_ptr = unsafe { (map.head() as *mut R).add(1) };
-        unsafe { *written_records_ptr = 0 };
+        let size = map.len();
         Self {
-            _map: map,
-            written_records_ptr: written_records_ptr,
-            end_ptr: end_ptr,
-            available: (size.get() - size_of::<u8>()) / size_of::<R>(),
+            map,
+            available: size.saturating_sub(records_offset::<R>()) / size_of::<R>(),
             index: HashMap::new(),
+            _records: PhantomData,
         }
     }
 
     pub fn put(&mut self, record: R) -> Result<()> {
-        if self.available > 0 {
-            let key = record.key();
-            let written_records = unsafe { self.written_records_ptr.read_volatile() };
-            match self.index.get(&key) {
-                Some(i) => {
-                    unsafe {
-                        self.end_ptr
-                            .sub(written_records - i + 1)
-                            .write_volatile(record)
-                    };
-                }
-                None => {
-                    unsafe {
-                        self.end_ptr.write(record);
-                        self.written_records_ptr.write_volatile(written_records + 1);
-                        self.end_ptr = self.end_ptr.add(1);
-                        self.index.insert(key, *self.written_records_ptr as usize);
-                    };
-                    self.available -= 1;
+        let key = record.key();
+        match self.index.get(&key) {
+            Some(i) => {
+                *self
+                    .map
+                    .view_mut::<R>(record_offset::<R>(*i))
+                    .map_err(|_| Errno::EFAULT)? = record;
+            }
+            None => {
+                if self.available == 0 {
+                    self.grow()?;
                 }
-            };
-            Ok(())
-        } else {
-            Err(Errno::ENOMEM)
-        }
+                let written_records = self.index.len();
+                *self
+                    .map
+                    .view_mut::<R>(record_offset::<R>(written_records))
+                    .map_err(|_| Errno::EFAULT)? = record;
+                self.map
+                    .view::<AtomicUsize>(WRITTEN_RECORDS_OFFSET)
+                    .map_err(|_| Errno::EFAULT)?
+                    .store(written_records + 1, Ordering::Release);
+                self.index.insert(key, written_records);
+                self.available -= 1;
+            }
+        };
+        Ok(())
+    }
+
+    /// Doubles the size of the shared memory once all the records slots are used.
+    fn grow(&mut self) -> Result<()> {
+        let size = self.map.len();
+        let new_size = self
+            .map
+            .definition
+            .size
+            .checked_mul(NonZero::new(2).expect("2 is not 0"))
+            .ok_or(Errno::ENOMEM)?;
+        self.map.grow(new_size).map_err(|_| Errno::ENOMEM)?;
+        self.available += (self.map.len() - size) / size_of::<R>();
+        Ok(())
     }
 }
 
@@ -109,7 +134,7 @@ impl<K: Eq + Hash, R: Record<K>> ShmDictionaryOwner<K, R> {
 pub trait Key: Eq + Hash + Clone {}
 
 /// Records stored in the dictionary need a key.
-pub trait Record<K>: Copy {
+pub trait Record<K>: Copy + ShmSafe {
     fn key(&self) -> K;
 }
 
@@ -117,7 +142,7 @@ pub trait Record<K>: Copy {
 mod tests {
     use std::num::NonZero;
 
-    use rshm::shm::ShmDefinition;
+    use rshm::shm::{ShmDefinition, ShmSafe};
 
     use crate::{Record, ShmDictionaryClient, ShmDictionaryOwner};
 
@@ -126,10 +151,13 @@ mod tests {
         pub value: (i32, i32),
     }
 
+    // Records only hold integers.
+    unsafe impl ShmSafe for TestRecord {}
+
     impl Record<i32> for TestRecord {
         fn key(&self) -> i32 {
             println!("{:?}", self.value);
-            self.value.0.clone()
+            self.value.0
         }
     }
 
@@ -138,6 +166,7 @@ mod tests {
         let owner_definition = ShmDefinition {
             path: "test_store".to_string(),
             size: NonZero::new(1024).expect("1024 is not 0"),
+            ..Default::default()
         };
         let owner_shared_memory = owner_definition.create().unwrap();
         let mut owner_store: ShmDictionaryOwner<i32, TestRecord> =
@@ -146,8 +175,9 @@ mod tests {
         let client_definition = ShmDefinition {
             path: "test_store".to_string(),
             size: NonZero::new(1024).expect("1024 is not 0"),
+            ..Default::default()
         };
-        let client_shared_memory = client_definition.open().unwrap();
+        let client_shared_memory = client_definition.open_read_only().unwrap();
         let mut client_store: ShmDictionaryClient<i32, TestRecord> =
             ShmDictionaryClient::new(client_shared_memory);
 
@@ -155,4 +185,30 @@ mod tests {
 
         assert_eq!(client_store.get(&1).unwrap().value, (1, 11));
     }
+
+    #[test]
+    fn store_grows_when_insertions_exceed_its_size() {
+        let owner_definition = ShmDefinition::new(
+            "test_store_growth",
+            NonZero::new(1024).expect("1024 is not 0"),
+        );
+        let owner_shared_memory = owner_definition.create().unwrap();
+        let mut owner_store: ShmDictionaryOwner<i32, TestRecord> =
+            ShmDictionaryOwner::new(owner_shared_memory);
+
+        let client_definition = ShmDefinition::new(
+            "test_store_growth",
+            NonZero::new(1024).expect("1024 is not 0"),
+        );
+        let client_shared_memory = client_definition.open_read_only().unwrap();
+        let mut client_store: ShmDictionaryClient<i32, TestRecord> =
+            ShmDictionaryClient::new(client_shared_memory);
+
+        for i in 0..300 {
+            owner_store.put(TestRecord { value: (i, i * 11) }).unwrap();
+        }
+
+        assert_eq!(client_store.get(&299).unwrap().value, (299, 3289));
+        assert_eq!(client_store.get(&1).unwrap().value, (1, 11));
+    }
 }
@@ -1,45 +1,29 @@
-use std::{
-    io::{Read, Write},
-    mem::size_of,
-};
+use std::io::{Read, Write};
 
-use rshm::shm::{OwnedShmMap, ShmMap};
+use rshm::shm::{OwnedShmMap, ReadOnlyShmMap};
 
 /// An ShmReader reads bytes from a shared memory buffer.
 /// There is no notification or wake-up mechanism built-in. This would have to be built
 /// separately. See the [LogConsumer] and [LogProducer] examples for a possible implementation.
 pub struct ShmReader {
-    _shm: ShmMap,
-    written_bytes_ptr: *const u8,
-    last_read_ptr: *const u8,
+    shm: ReadOnlyShmMap,
     read: usize,
 }
 
 impl ShmReader {
-    pub fn new(shm: ShmMap) -> Self {
-        // We keep the number of written bytes of the beginning
-        let written_bytes_ptr = shm.head();
-        let last_read_ptr = unsafe { written_bytes_ptr.add(1) };
-        Self {
-            _shm: shm,
-            written_bytes_ptr: written_bytes_ptr,
-            last_read_ptr: last_read_ptr,
-            read: 0,
-        }
+    pub fn new(shm: ReadOnlyShmMap) -> Self {
+        Self { shm, read: 0 }
     }
 }
 
 impl Read for ShmReader {
     fn read(&mut self, out: &mut [u8]) -> std::result::Result<usize, std::io::Error> {
-        let readable_size = out
-            .len()
-            .min(unsafe { *self.written_bytes_ptr as usize } - self.read);
+        // We keep the number of written bytes of the beginning
+        let (written_bytes, bytes) = self.shm.as_slice().split_at(1);
+        let readable_size = out.len().min(written_bytes[0] as usize - self.read);
         if readable_size > 0 {
-            unsafe {
-                self.last_read_ptr.copy_to(out.as_mut_ptr(), readable_size);
-                self.last_read_ptr = self.last_read_ptr.add(readable_size);
-            }
-            self.read = self.read + readable_size;
+            out[..readable_size].copy_from_slice(&bytes[self.read..self.read + readable_size]);
+            self.read += readable_size;
             Ok(readable_size)
         } else {
             Ok(0)
@@ -48,39 +32,27 @@ impl Read for ShmReader {
 }
 
 pub struct ShmWriter {
-    _shm: OwnedShmMap,
-    written_bytes_ptr: *mut u8,
-    end_ptr: *mut u8,
-    available: usize,
+    shm: OwnedShmMap,
+    written: usize,
 }
 
 impl ShmWriter {
-    pub fn new(shm: OwnedShmMap) -> Self {
-        let available = shm.definition.size.get() - size_of::<u8>();
-
-        // We keep the number of written bytes of the beginning
-        let written_bytes_ptr = shm.head() as *mut u8;
-        let end_ptr = unsafe { written_bytes_ptr.add(1) } as *mut u8;
-        unsafe { *written_bytes_ptr = 0 };
-        Self {
-            _shm: shm,
-            written_bytes_ptr,
-            end_ptr,
-            available,
-        }
+    pub fn new(mut shm: OwnedShmMap) -> Self {
+        shm.as_mut_slice()[0] = 0;
+        Self { shm, written: 0 }
     }
 }
 
 impl Write for ShmWriter {
     fn write(&mut self, value: &[u8]) -> std::result::Result<usize, std::io::Error> {
-        let writable_size = self.available.min(value.len());
+        // We keep the number of written bytes of the beginning
+        let (written_bytes, bytes) = self.shm.as_mut_slice().split_at_mut(1);
+        let writable_size = (bytes.len() - self.written).min(value.len());
         if writable_size > 0 {
-            unsafe {
-                self.end_ptr.copy_from(value.as_ptr(), writable_size);
-                self.end_ptr = self.end_ptr.add(writable_size);
-                *self.written_bytes_ptr += writable_size as u8;
-            }
-            self.available = self.available - writable_size;
+            bytes[self.written..self.written + writable_size]
+                .copy_from_slice(&value[..writable_size]);
+            self.written += writable_size;
+            written_bytes[0] += writable_size as u8;
             Ok(writable_size)
         } else {
             Ok(0)
@@ -107,20 +79,22 @@ mod tests {
         let writer_definition = ShmDefinition {
             path: "test_writer".to_string(),
             size: NonZero::new(10).expect("10 is not 0"),
+            ..Default::default()
         };
         let writer_shm = writer_definition.create().unwrap();
         let mut writer = ShmWriter::new(writer_shm);
 
-        writer.write("test1".as_bytes()).unwrap();
+        writer.write_all("test1".as_bytes()).unwrap();
         writer.flush().unwrap();
 
         let reader_definition = ShmDefinition {
             path: "test_writer".to_string(),
             size: NonZero::new(10).expect("10 is not 0"),
+            ..Default::default()
         };
-        let reader_shm = reader_definition.open().unwrap();
+        let reader_shm = reader_definition.open_read_only().unwrap();
         let mut reader = ShmReader::new(reader_shm);
-        let mut reader_buffer = vec![0 as u8; 1024];
+        let mut reader_buffer = vec![0_u8; 1024];
         let count = reader.read(&mut reader_buffer).unwrap();
 
         assert_eq!(
@@ -1,43 +1,45 @@
 /// This example shows how the rshm library can be used to create a log with a single producer
 /// and multiple consumers, using shared condvars to notify consumers of a new record in the log.
 ///
-use std::mem::size_of;
+use std::mem::{align_of, size_of};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 use rshm::{
     condvar::Condvar,
-    shm::{OwnedShmMap, ShmMap},
+    shm::{OwnedShmMap, ReadOnlyShmMap, ShmSafe},
 };
 
+/// The offset of the condvar signalling new records.
+const CONDVAR_OFFSET: usize = 0;
+/// The offset of the sequence number of the last record.
+const SEQUENCE_OFFSET: usize = size_of::<u64>();
+
+/// The offset of the first record, past the sequence number and aligned for the records.
+fn records_offset<E>() -> usize {
+    (SEQUENCE_OFFSET + size_of::<u64>()).next_multiple_of(align_of::<E>())
+}
+
 /// A LogConsumer reads records from the log as they become available,
 /// as signalled by a LogProducer through a condvar.
-pub struct LogConsumer<E: Copy> {
-    _map: ShmMap,
-    condvar: *const Condvar,
-    sequence_number: *const u64,
-    end_ptr: *const E,
+pub struct LogConsumer<E: Copy + ShmSafe> {
+    map: ReadOnlyShmMap,
+    next_offset: usize,
     next_sequence: u64,
+    _records: std::marker::PhantomData<E>,
 }
 
-impl<E: Copy> LogConsumer<E> {
-    /// Creates a new LogConsumer from the given [rshm::shm::ShmMap].
+impl<E: Copy + ShmSafe> LogConsumer<E> {
+    /// Creates a new LogConsumer from the given [rshm::shm::ReadOnlyShmMap].
     /// The memory block is expected to contain:
     /// * a [rshm::condvar::Condvar] used to wait for available records
     /// * a [u64] sequence number indicating the last record's index
     /// * aligned records in sequence order
-    pub fn new(map: ShmMap) -> Self {
-        let condvar_ptr = map.head() as *const Condvar;
-        // We keep the number of written bytes of the beginning
-        let sequence_number =
-            unsafe { (condvar_ptr.add(condvar_ptr.align_offset(size_of::<u64>()))) as *mut u64 };
-        // Ensure Alignment
-        let alignment_offset = sequence_number.align_offset(size_of::<E>());
-        let end_ptr = unsafe { sequence_number.add(alignment_offset.max(1)) as *mut E };
+    pub fn new(map: ReadOnlyShmMap) -> Self {
         Self {
-            _map: map,
-            condvar: condvar_ptr,
-            sequence_number,
-            end_ptr,
+            map,
+            next_offset: records_offset::<E>(),
             next_sequence: 1,
+            _records: std::marker::PhantomData,
         }
     }
 
@@ -47,25 +49,25 @@ impl<E: Copy> LogConsumer<E> {
     /// It will return
     /// * Some(record) when a record was read
     /// * None when the wait is interrupted or when the condition changes but no
-    /// new records are available (the current sequence in shared memory is still lower than the next
-    /// sequence we expect to read)
+    ///   new records are available (the current sequence in shared memory is still lower than the next
+    ///   sequence we expect to read)
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<E> {
-        let mut current_sequence = unsafe { self.sequence_number.read_volatile() };
+        let condvar = self.map.view::<Condvar>(CONDVAR_OFFSET).ok()?;
+        let sequence_number = self.map.view::<AtomicU64>(SEQUENCE_OFFSET).ok()?;
+        let mut current_sequence = sequence_number.load(Ordering::Acquire);
         if current_sequence < self.next_sequence {
-            match unsafe { (*self.condvar).wait() } {
+            match condvar.wait() {
                 Err(_) => return None,
                 _ => {
-                    current_sequence = unsafe { self.sequence_number.read_volatile() };
+                    current_sequence = sequence_number.load(Ordering::Acquire);
                 }
             }
         }
         if current_sequence >= self.next_sequence {
-            let record = unsafe { self.end_ptr.read_volatile() };
+            let record = *self.map.view::<E>(self.next_offset).ok()?;
             self.next_sequence += 1;
-
-            unsafe {
-                self.end_ptr = self.end_ptr.add(1);
-            }
+            self.next_offset += size_of::<E>();
             Some(record)
         } else {
             None
@@ -74,55 +76,45 @@ impl<E: Copy> LogConsumer<E> {
 }
 
 /// A LogProducer writes records into the log and signals new data is available through a Condvar.
-pub struct LogProducer<E: Copy> {
-    _map: OwnedShmMap,
-    condvar: *const Condvar,
-    sequence_number: *mut u64,
-    end_ptr: *mut E,
-    available: usize,
+pub struct LogProducer<E: Copy + ShmSafe> {
+    map: OwnedShmMap,
+    next_offset: usize,
+    _records: std::marker::PhantomData<E>,
 }
 
-impl<E: Copy> LogProducer<E> {
+impl<E: Copy + ShmSafe> LogProducer<E> {
     /// Creates a new LogProducer using the given [rshm::shm::OwnedShmMap].
     /// The memory block will contain:
     /// * a [rshm::condvar::Condvar] used to signal the availability of records
     /// * a [u64] sequence number indicating the last record's index
     /// * aligned records in sequence order
     pub fn new(map: OwnedShmMap) -> Self {
-        let condvar_ptr = map.head() as *const Condvar;
-        // We keep the number of written bytes of the beginning
-        let sequence_number =
-            unsafe { (condvar_ptr.add(condvar_ptr.align_offset(size_of::<u64>()))) as *mut u64 };
-        // Ensure Alignment
-        let alignment_offset = sequence_number.align_offset(size_of::<E>());
-        let end_ptr = unsafe { sequence_number.add(alignment_offset.max(1)) as *mut E };
-        let map_size = map.definition.size;
         Self {
-            _map: map,
-            condvar: condvar_ptr,
-            sequence_number,
-            end_ptr,
-            available: map_size.get() / size_of::<E>() - alignment_offset,
+            map,
+            next_offset: records_offset::<E>(),
+            _records: std::marker::PhantomData,
         }
     }
 
     /// inserts a new record at the end of the log.
     /// The sequence number will be incremented and the condvar will be notified.
     pub fn insert(&mut self, record: E) -> Result<(), ErrorCode> {
-        if self.available > 0 {
-            let sequence_number = unsafe { self.sequence_number.read_volatile() };
-            unsafe {
-                self.end_ptr.write(record);
-                self.sequence_number.write_volatile(sequence_number + 1);
-                self.end_ptr = self.end_ptr.add(1);
-            };
-            self.available -= 1;
-            unsafe { (*self.condvar).notify_all() }
-                .map(|_| ())
-                .map_err(|_| ErrorCode::NotifyAllFailed)
-        } else {
-            Err(ErrorCode::NoSpaceLeftInSharedMemory)
-        }
+        *self
+            .map
+            .view_mut::<E>(self.next_offset)
+            .map_err(|_| ErrorCode::NoSpaceLeftInSharedMemory)? = record;
+        self.next_offset += size_of::<E>();
+        let sequence_number = self
+            .map
+            .view::<AtomicU64>(SEQUENCE_OFFSET)
+            .map_err(|_| ErrorCode::NoSpaceLeftInSharedMemory)?;
+        sequence_number.fetch_add(1, Ordering::Release);
+        self.map
+            .view::<Condvar>(CONDVAR_OFFSET)
+            .map_err(|_| ErrorCode::NoSpaceLeftInSharedMemory)?
+            .notify_all()
+            .map(|_| ())
+            .map_err(|_| ErrorCode::NotifyAllFailed)
     }
 }
 
@@ -147,6 +139,7 @@ mod tests {
         let definition_producer = ShmDefinition {
             path: "test".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let producer_shm = definition_producer.create().unwrap();
         let mut producer = LogProducer::new(producer_shm);
@@ -155,8 +148,9 @@ mod tests {
             let definition_consumer = ShmDefinition {
                 path: "test".to_string(),
                 size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+                ..Default::default()
             };
-            let consumer_shm = definition_consumer.open().unwrap();
+            let consumer_shm = definition_consumer.open_read_only().unwrap();
             let mut consumer = LogConsumer::new(consumer_shm);
             consumer.next().unwrap()
         });
@@ -0,0 +1,573 @@
+//!
+//! A command-line tool to inspect and operate on the shared memory objects of /dev/shm.
+//!
+//! The `tail`, `get` and `dump` subcommands read the formats of the log and dictionary examples:
+//! * a log starts with a condvar and a u64 sequence number, followed by aligned records
+//! * a dictionary starts with a usize number of records, followed by aligned records
+//!
+use std::fmt::Write as _;
+use std::num::NonZero;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand};
+use rshm::shm::{list_in, ErrorCode, ReadOnlyShmMap, SegmentInfo, ShmDefinition};
+use rshm::Error;
+
+/// Inspect and operate on shared memory segments.
+#[derive(Parser, Debug)]
+#[command(name = "rshm", version, about)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the segments of a directory
+    Ls {
+        /// The directory holding the segments (e.g. a hugetlbfs mount)
+        #[arg(long, default_value = "/dev/shm")]
+        dir: PathBuf,
+    },
+    /// Describe a segment and its header
+    Stat {
+        /// The name of the segment
+        name: String,
+    },
+    /// Create a segment that outlives this command
+    Create {
+        /// The name of the segment
+        name: String,
+        /// The size of the segment in bytes, including its header
+        size: NonZero<usize>,
+        /// Start the segment with an rshm header (marked as initialized)
+        #[arg(long)]
+        header: bool,
+        /// The permissions of the segment, in octal
+        #[arg(long, value_parser = parse_mode)]
+        mode: Option<u32>,
+    },
+    /// Remove a segment
+    Rm {
+        /// The name of the segment
+        name: String,
+    },
+    /// Display the content of a segment in hexadecimal and ascii
+    Hexdump {
+        /// The name of the segment
+        name: String,
+        /// The offset of the first byte to display, from the start of the segment
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+        /// The number of bytes to display, up to the end of the segment by default
+        #[arg(long)]
+        length: Option<usize>,
+    },
+    /// Remove the segments whose owner died
+    Gc {
+        /// List the stale segments without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Display the last records of a log segment
+    Tail {
+        /// The name of the segment
+        name: String,
+        #[command(flatten)]
+        record: RecordArgs,
+        /// The number of records to display
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: u64,
+        /// Keep displaying records as they are added to the log
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Display the record of the given key in a dictionary segment
+    Get {
+        /// The name of the segment
+        name: String,
+        /// The key, an integer stored in little endian in the key bytes of the records
+        key: i128,
+        #[command(flatten)]
+        record: RecordArgs,
+        /// The offset of the key in the records
+        #[arg(long, default_value_t = 0)]
+        key_offset: usize,
+        /// The size of the key in bytes
+        #[arg(long, default_value_t = 8)]
+        key_size: usize,
+    },
+    /// Display all the records of a dictionary segment
+    Dump {
+        /// The name of the segment
+        name: String,
+        #[command(flatten)]
+        record: RecordArgs,
+    },
+}
+
+/// The layout of the records of a log or dictionary segment.
+#[derive(clap::Args, Debug)]
+struct RecordArgs {
+    /// The size of the records in bytes
+    #[arg(long)]
+    record_size: NonZero<usize>,
+    /// The alignment of the records in bytes
+    #[arg(long, default_value_t = 8)]
+    record_align: usize,
+}
+
+impl RecordArgs {
+    /// The offset of the first record, past a preamble of the given size.
+    fn records_offset(&self, preamble_size: usize) -> Option<usize> {
+        preamble_size.checked_next_multiple_of(self.record_align.max(1))
+    }
+
+    /// The offset of the given number of records, following the first one.
+    fn records_end(&self, records_offset: usize, count: usize) -> Option<usize> {
+        count
+            .checked_mul(self.record_size.get())
+            .and_then(|size| size.checked_add(records_offset))
+    }
+}
+
+/// How long `tail --follow` waits before checking for new records.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(10);
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("rshm: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Ls { dir } => ls(dir),
+        Command::Stat { name } => stat(&name),
+        Command::Create {
+            name,
+            size,
+            header,
+            mode,
+        } => create(name, size, header, mode),
+        Command::Rm { name } => rm(&name),
+        Command::Hexdump {
+            name,
+            offset,
+            length,
+        } => hexdump(&name, offset, length),
+        Command::Gc { dry_run } => gc(dry_run),
+        Command::Tail {
+            name,
+            record,
+            lines,
+            follow,
+        } => tail(&name, &record, lines, follow),
+        Command::Get {
+            name,
+            key,
+            record,
+            key_offset,
+            key_size,
+        } => get(&name, key, &record, key_offset, key_size),
+        Command::Dump { name, record } => dump(&name, &record),
+    }
+}
+
+fn ls(dir: PathBuf) -> Result<(), String> {
+    let segments = list_in(&dir).map_err(|error| describe(&dir.to_string_lossy(), error))?;
+    println!(
+        "{:<32} {:>12} {:>6} {:>6} {:>6} {:>8} {:>8}",
+        "NAME", "SIZE", "MODE", "UID", "GID", "OWNER", "STATE"
+    );
+    for segment in segments {
+        let (owner, state) = match &segment.header {
+            None => ("-".to_string(), "-"),
+            Some(header) => (
+                header
+                    .owner
+                    .map(|pid| pid.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                if header.stale {
+                    "stale"
+                } else if header.initialized {
+                    "ready"
+                } else {
+                    "created"
+                },
+            ),
+        };
+        println!(
+            "{:<32} {:>12} {:>6o} {:>6} {:>6} {:>8} {:>8}",
+            segment.name, segment.size, segment.mode, segment.uid, segment.gid, owner, state
+        );
+    }
+    Ok(())
+}
+
+fn stat(name: &str) -> Result<(), String> {
+    let segment = find(name)?;
+    println!("name:      {}", segment.name);
+    println!("path:      {}", segment.path.display());
+    println!("size:      {}", segment.size);
+    println!("mode:      {:o}", segment.mode);
+    println!("uid:       {}", segment.uid);
+    println!("gid:       {}", segment.gid);
+    println!("modified:  {}", seconds_since_epoch(segment.modified));
+    match segment.header {
+        None => println!("header:    none"),
+        Some(header) => {
+            println!("header:    version {}", header.version);
+            println!("  size:           {}", header.size);
+            println!("  payload offset: {}", header.payload_offset);
+            println!("  fingerprint:    {:#018x}", header.fingerprint);
+            println!("  created:        {}", seconds_since_epoch(header.created));
+            println!("  generation:     {}", header.generation);
+            println!("  initialized:    {}", header.initialized);
+            match header.owner {
+                None => println!("  owner:          none"),
+                Some(pid) if header.stale => println!("  owner:          {pid} (dead)"),
+                Some(pid) => println!("  owner:          {pid}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn create(
+    name: String,
+    size: NonZero<usize>,
+    header: bool,
+    mode: Option<u32>,
+) -> Result<(), String> {
+    let mut definition = ShmDefinition::new(name.clone(), size);
+    if header {
+        definition = definition.with_header();
+    }
+    if let Some(mode) = mode {
+        definition = definition.with_mode(mode);
+    }
+    let owned_shm = definition
+        .create()
+        .map_err(|error| describe(&name, error))?;
+    if header {
+        owned_shm
+            .mark_initialized()
+            .map_err(|error| describe(&name, error))?;
+    }
+    // The segment outlives this command.
+    owned_shm
+        .into_unowned()
+        .close()
+        .map_err(|error| describe(&name, error))
+}
+
+fn rm(name: &str) -> Result<(), String> {
+    open(name, false)?
+        .unlink()
+        .map_err(|error| describe(name, error))
+}
+
+fn hexdump(name: &str, offset: usize, length: Option<usize>) -> Result<(), String> {
+    let shm = open(name, false)?;
+    let bytes = shm.as_slice();
+    let start = offset.min(bytes.len());
+    let end = length
+        .map(|length| start.saturating_add(length).min(bytes.len()))
+        .unwrap_or(bytes.len());
+    print!("{}", format_hexdump(&bytes[start..end], start));
+    Ok(())
+}
+
+fn gc(dry_run: bool) -> Result<(), String> {
+    let segments = list_in("/dev/shm").map_err(|error| describe("/dev/shm", error))?;
+    let mut failed = false;
+    for segment in segments {
+        if !segment.header.as_ref().is_some_and(|header| header.stale) {
+            continue;
+        }
+        if dry_run {
+            println!("{}", segment.name);
+            continue;
+        }
+        match ShmDefinition::new(segment.name.clone(), NonZero::<usize>::MIN).unlink_stale() {
+            Ok(()) => println!("removed {}", segment.name),
+            Err(error) => {
+                eprintln!("rshm: {}", describe(&segment.name, error));
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        Err("some stale segments could not be removed".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn tail(name: &str, record: &RecordArgs, lines: u64, follow: bool) -> Result<(), String> {
+    let shm = open_payload(name)?;
+    // The log starts with a condvar, followed by the sequence number of the last record.
+    let sequence_offset = size_of::<u64>();
+    let out_of_bounds = || {
+        describe(
+            name,
+            ErrorCode::ViewOutOfBounds {
+                end: usize::MAX,
+                len: shm.len(),
+            },
+        )
+    };
+    let records_offset = record
+        .records_offset(sequence_offset + size_of::<u64>())
+        .ok_or_else(out_of_bounds)?;
+    let sequence_number = shm
+        .view::<AtomicU64>(sequence_offset)
+        .map_err(|error| describe(name, error))?;
+    let mut next_sequence = sequence_number
+        .load(Ordering::Acquire)
+        .saturating_sub(lines)
+        .saturating_add(1);
+    loop {
+        let last_sequence = sequence_number.load(Ordering::Acquire);
+        while next_sequence <= last_sequence {
+            let offset = usize::try_from(next_sequence - 1)
+                .ok()
+                .and_then(|index| record.records_end(records_offset, index))
+                .ok_or_else(out_of_bounds)?;
+            let bytes = shm
+                .view_array::<u8>(offset, record.record_size.get())
+                .map_err(|error| describe(name, error))?;
+            println!("{next_sequence:>10}  {}", format_bytes(bytes));
+            next_sequence += 1;
+        }
+        if !follow {
+            return Ok(());
+        }
+        // The sequence number is checked again after a bounded wait, rather than waiting on the
+        // condvar of the log: a record added between the check and the wait would be missed.
+        std::thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+fn get(
+    name: &str,
+    key: i128,
+    record: &RecordArgs,
+    key_offset: usize,
+    key_size: usize,
+) -> Result<(), String> {
+    if key_size == 0
+        || key_size > size_of::<i128>()
+        || key_offset
+            .checked_add(key_size)
+            .is_none_or(|key_end| key_end > record.record_size.get())
+    {
+        return Err(format!(
+            "the key ({key_size} bytes at {key_offset}) does not fit in the records"
+        ));
+    }
+    let key_bytes = &key.to_le_bytes()[..key_size];
+    let shm = open_payload(name)?;
+    for (index, bytes) in dictionary_records(name, &shm, record)? {
+        if &bytes[key_offset..key_offset + key_size] == key_bytes {
+            println!("{index:>10}  {}", format_bytes(bytes));
+            return Ok(());
+        }
+    }
+    Err(format!("{name}: no record with key {key}"))
+}
+
+fn dump(name: &str, record: &RecordArgs) -> Result<(), String> {
+    let shm = open_payload(name)?;
+    for (index, bytes) in dictionary_records(name, &shm, record)? {
+        println!("{index:>10}  {}", format_bytes(bytes));
+    }
+    Ok(())
+}
+
+/// The records of a dictionary, which starts with the number of records.
+fn dictionary_records<'a>(
+    name: &str,
+    shm: &'a ReadOnlyShmMap,
+    record: &RecordArgs,
+) -> Result<impl Iterator<Item = (usize, &'a [u8])>, String> {
+    let count = shm
+        .view::<AtomicUsize>(0)
+        .map_err(|error| describe(name, error))?
+        .load(Ordering::Acquire);
+    let out_of_bounds = || {
+        describe(
+            name,
+            ErrorCode::ViewOutOfBounds {
+                end: usize::MAX,
+                len: shm.len(),
+            },
+        )
+    };
+    let offset = record
+        .records_offset(size_of::<usize>())
+        .ok_or_else(out_of_bounds)?;
+    let len = record
+        .records_end(0, count)
+        .filter(|len| offset.checked_add(*len).is_some())
+        .ok_or_else(out_of_bounds)?;
+    let bytes = shm
+        .view_array::<u8>(offset, len)
+        .map_err(|error| describe(name, error))?;
+    Ok(bytes.chunks_exact(record.record_size.get()).enumerate())
+}
+
+fn find(name: &str) -> Result<SegmentInfo, String> {
+    list_in("/dev/shm")
+        .map_err(|error| describe("/dev/shm", error))?
+        .into_iter()
+        .find(|segment| segment.name == name)
+        .ok_or_else(|| describe(name, ErrorCode::ShmPathDoesNotExist))
+}
+
+/// Opens the whole segment, including its header if any.
+fn open(name: &str, header: bool) -> Result<ReadOnlyShmMap, String> {
+    let mut definition = ShmDefinition::new(name, NonZero::<usize>::MIN).with_discovered_size();
+    if header {
+        definition = definition.with_header();
+    }
+    definition
+        .open_read_only()
+        .map_err(|error| describe(name, error))
+}
+
+/// Opens the segment past its header, if it has one.
+fn open_payload(name: &str) -> Result<ReadOnlyShmMap, String> {
+    let header = find(name)?.header.is_some();
+    open(name, header)
+}
+
+fn describe(name: &str, error: impl Into<Error>) -> String {
+    let error = error.into();
+    match error.path() {
+        Some(_) => error.to_string(),
+        None => format!("{name}: {error}"),
+    }
+}
+
+fn parse_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode.trim_start_matches("0o"), 8).map_err(|error| error.to_string())
+}
+
+fn seconds_since_epoch(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| format!("{}.{:09}", elapsed.as_secs(), elapsed.subsec_nanos()))
+        .unwrap_or_else(|_| "-".to_string())
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut line, byte| {
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        let _ = write!(line, "{byte:02x}");
+        line
+    })
+}
+
+/// Formats the bytes as `hexdump -C` does, squeezing repeated lines into a single `*`.
+fn format_hexdump(bytes: &[u8], start: usize) -> String {
+    let mut output = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut squeezing = false;
+    for (i, line) in bytes.chunks(16).enumerate() {
+        if previous == Some(line) {
+            if !squeezing {
+                output.push_str("*\n");
+                squeezing = true;
+            }
+            continue;
+        }
+        squeezing = false;
+        previous = Some(line);
+        let _ = write!(output, "{:08x}  ", start + i * 16);
+        for column in 0..16 {
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(output, "{byte:02x} ");
+                }
+                None => output.push_str("   "),
+            }
+            if column == 7 {
+                output.push(' ');
+            }
+        }
+        output.push_str(" |");
+        output.extend(line.iter().map(|&byte| {
+            if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '.'
+            }
+        }));
+        output.push_str("|\n");
+    }
+    let _ = writeln!(output, "{:08x}", start + bytes.len());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::{format_hexdump, parse_mode, RecordArgs};
+
+    #[test]
+    fn format_hexdump_squeezes_repeated_lines() {
+        let mut bytes = b"rshm_seg".to_vec();
+        bytes.resize(64, 0);
+
+        assert_eq!(
+            "00000000  72 73 68 6d 5f 73 65 67  00 00 00 00 00 00 00 00  |rshm_seg........|\n\
+             00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+             *\n\
+             00000040\n",
+            format_hexdump(&bytes, 0)
+        );
+    }
+
+    #[test]
+    fn format_hexdump_pads_the_last_line() {
+        assert_eq!(
+            "00000010  61 62 63                                          |abc|\n00000013\n",
+            format_hexdump(b"abc", 16)
+        );
+    }
+
+    #[test]
+    fn parse_mode_reads_octal_permissions() {
+        assert_eq!(Ok(0o640), parse_mode("640"));
+        assert_eq!(Ok(0o600), parse_mode("0o600"));
+    }
+
+    #[test]
+    fn record_offsets_report_an_overflow() {
+        let record = RecordArgs {
+            record_size: NonZero::new(1 << 20).unwrap(),
+            record_align: 16,
+        };
+
+        assert_eq!(Some(16), record.records_offset(9));
+        assert_eq!(Some(16 + (3 << 20)), record.records_end(16, 3));
+        assert_eq!(None, record.records_end(16, usize::MAX >> 10));
+        let record = RecordArgs {
+            record_align: 1 << 63,
+            ..record
+        };
+        assert_eq!(None, record.records_offset((1 << 63) + 1));
+    }
+}
@@ -1,8 +1,13 @@
 #![cfg(target_os = "linux")]
 
+use nix::errno::Errno;
+
+use crate::Error;
+
 use std::{
     ptr::null,
     sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
 };
 
 ///
@@ -14,12 +19,30 @@ pub struct Condvar {
     inner: Futex,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ErrorCode {
     WaitInterrupted,
     InvalidWakeArguments,
 }
 
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCode::WaitInterrupted => write!(f, "the wait was interrupted"),
+            ErrorCode::InvalidWakeArguments => write!(f, "the wake arguments are invalid"),
+        }
+    }
+}
+
+// A Condvar is a futex word, which any bit pattern is valid for (all zeroes being a new Condvar).
+unsafe impl crate::shm::ShmSafe for Condvar {}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Condvar {
     ///
     /// Create a new futex based Condvar.
@@ -54,7 +77,7 @@ impl Condvar {
     ///  assert_eq!(1, waking_thread.join().unwrap());
     /// ```
     ///
-    pub fn wait(&self) -> Result<(), ErrorCode> {
+    pub fn wait(&self) -> Result<(), Error> {
         unsafe { self.inner.wait() }
     }
 
@@ -86,8 +109,8 @@ impl Condvar {
     ///  assert_eq!(2, waking_thread.join().unwrap());
     /// ```
     ///
-    pub fn notify_all(&self) -> Result<i32, ErrorCode> {
-        unsafe { self.inner.wake(libc::INT_MAX) }
+    pub fn notify_all(&self) -> Result<i32, Error> {
+        unsafe { self.inner.wake(libc::c_int::MAX) }
     }
 }
 
@@ -97,7 +120,7 @@ struct Futex {
 }
 
 impl Futex {
-    unsafe fn wait(&self) -> Result<(), ErrorCode> {
+    unsafe fn wait(&self) -> Result<(), Error> {
         let expected_value = self.value.load(Ordering::Acquire);
         while expected_value >= self.value.load(Ordering::Acquire) {
             let result = libc::syscall(
@@ -105,39 +128,89 @@ impl Futex {
                 &self.value,
                 libc::FUTEX_WAIT,
                 expected_value,
-                null() as *const libc::timespec,
-                null() as *const AtomicI32,
+                null::<libc::timespec>(),
+                null::<AtomicI32>(),
                 0,
-            ) as i32;
-            if result == libc::EINTR {
-                return Err(ErrorCode::WaitInterrupted);
+            );
+            // The syscall returns -1 and sets errno on failure.
+            if result == -1 && Errno::last() == Errno::EINTR {
+                return Err(Error::from_errno(
+                    ErrorCode::WaitInterrupted,
+                    "futex",
+                    Errno::EINTR,
+                ));
             }
         }
         Ok(())
     }
 
-    unsafe fn wake(&self, count: i32) -> Result<i32, ErrorCode> {
+    unsafe fn wake(&self, count: i32) -> Result<i32, Error> {
         self.value.fetch_add(1, Ordering::Release);
         let result = libc::syscall(
             libc::SYS_futex,
             &self.value,
             libc::FUTEX_WAKE,
             count,
-            null() as *const libc::timespec,
-            null() as *const AtomicI32,
+            null::<libc::timespec>(),
+            null::<AtomicI32>(),
             0,
-        ) as i32;
-        if result == libc::EINVAL {
-            Err(ErrorCode::InvalidWakeArguments)
+        );
+        if result == -1 {
+            Err(Error::from_errno(
+                ErrorCode::InvalidWakeArguments,
+                "futex",
+                Errno::last(),
+            ))
         } else {
-            Ok(result)
+            Ok(result as i32)
         }
     }
 }
 
+///
+/// Blocks the current thread while the given shared futex word holds the expected value,
+/// for at most the given timeout. Wake-ups may be spurious: callers check the value again.
+///
+pub(crate) fn futex_wait(value: &AtomicI32, expected: i32, timeout: Duration) {
+    let timeout = libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    };
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            value,
+            libc::FUTEX_WAIT,
+            expected,
+            &timeout as *const libc::timespec,
+            null::<AtomicI32>(),
+            0,
+        )
+    };
+}
+
+///
+/// Wakes all the threads waiting on the given shared futex word.
+///
+pub(crate) fn futex_wake_all(value: &AtomicI32) {
+    unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            value,
+            libc::FUTEX_WAKE,
+            libc::c_int::MAX,
+            null::<libc::timespec>(),
+            null::<AtomicI32>(),
+            0,
+        )
+    };
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Futex;
+    use super::{ErrorCode, Futex};
+    use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+    use std::os::unix::thread::JoinHandleExt;
     use std::sync::atomic::AtomicI32;
     use std::sync::Arc;
     use std::thread;
@@ -159,4 +232,30 @@ mod tests {
         unsafe { futex.wait().unwrap() };
         assert_eq!(1, waking_thread.join().unwrap());
     }
+
+    extern "C" fn ignore_signal(_: libc::c_int) {}
+
+    #[test]
+    fn futex_wait_reports_an_interruption_by_a_signal() {
+        // Without SA_RESTART, the wait is interrupted by the handled signal.
+        let action = SigAction::new(
+            SigHandler::Handler(ignore_signal),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        unsafe { sigaction(Signal::SIGUSR1, &action) }.unwrap();
+        let futex = Arc::new(Futex {
+            value: AtomicI32::new(0),
+        });
+        let futex_clone = futex.clone();
+        let waiting_thread = thread::spawn(move || unsafe { futex_clone.wait() });
+
+        while !waiting_thread.is_finished() {
+            unsafe { libc::pthread_kill(waiting_thread.as_pthread_t(), libc::SIGUSR1) };
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        let error = waiting_thread.join().unwrap().unwrap_err();
+        assert_eq!(ErrorCode::WaitInterrupted, error);
+    }
 }
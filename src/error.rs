@@ -0,0 +1,271 @@
+use std::fmt;
+
+use nix::errno::Errno;
+
+use crate::{condvar, shm};
+
+///
+/// The error reported by the operations of this crate.
+///
+/// It carries the kind of error, along with the operation (e.g. the system call) that failed,
+/// the path of the shared memory object and the errno reported by the system, when known.
+///
+/// ```
+/// use rshm::shm::{ErrorCode, ShmDefinition};
+///
+/// let definition = ShmDefinition::new("example_error", std::num::NonZero::new(1024).unwrap());
+/// let error = definition.open().unwrap_err();
+/// assert_eq!(ErrorCode::ShmPathDoesNotExist, error);
+/// assert_eq!(Some("shm_open"), error.operation());
+/// assert_eq!(Some("example_error"), error.path());
+/// assert_eq!(
+///     "shm_open example_error failed: the shared memory object does not exist (ENOENT: No such file or directory)",
+///     error.to_string()
+/// );
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    kind: ErrorKind,
+    operation: Option<&'static str>,
+    path: Option<String>,
+    errno: Option<Errno>,
+}
+
+///
+/// The kinds of errors reported by the modules of this crate.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    /// An error of a shared memory object.
+    Shm(shm::ErrorCode),
+    /// An error of a condvar.
+    Condvar(condvar::ErrorCode),
+}
+
+impl Error {
+    ///
+    /// Creates the error of the given kind reported by a failed system call.
+    ///
+    pub(crate) fn from_errno(
+        kind: impl Into<ErrorKind>,
+        operation: &'static str,
+        errno: Errno,
+    ) -> Self {
+        Error {
+            kind: kind.into(),
+            operation: Some(operation),
+            path: None,
+            errno: Some(errno),
+        }
+    }
+
+    ///
+    /// Records the path of the shared memory object the error occurred on, unless it is known.
+    ///
+    pub(crate) fn with_path(self, path: &str) -> Self {
+        Error {
+            path: self.path.or_else(|| Some(path.to_string())),
+            ..self
+        }
+    }
+
+    /// returns the kind of error
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// returns the code of a shared memory error
+    pub fn code(&self) -> Option<&shm::ErrorCode> {
+        match &self.kind {
+            ErrorKind::Shm(code) => Some(code),
+            ErrorKind::Condvar(_) => None,
+        }
+    }
+
+    /// returns the operation that failed (e.g. shm_open), when known
+    pub fn operation(&self) -> Option<&'static str> {
+        self.operation
+    }
+
+    /// returns the path of the shared memory object the error occurred on, when known
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// returns the errno reported by the system, when the error comes from a system call
+    pub fn errno(&self) -> Option<Errno> {
+        self.errno
+    }
+}
+
+impl From<shm::ErrorCode> for ErrorKind {
+    fn from(code: shm::ErrorCode) -> Self {
+        ErrorKind::Shm(code)
+    }
+}
+
+impl From<condvar::ErrorCode> for ErrorKind {
+    fn from(code: condvar::ErrorCode) -> Self {
+        ErrorKind::Condvar(code)
+    }
+}
+
+impl From<shm::ErrorCode> for Error {
+    fn from(code: shm::ErrorCode) -> Self {
+        Error {
+            kind: ErrorKind::Shm(code),
+            operation: None,
+            path: None,
+            errno: None,
+        }
+    }
+}
+
+impl From<condvar::ErrorCode> for Error {
+    fn from(code: condvar::ErrorCode) -> Self {
+        Error {
+            kind: ErrorKind::Condvar(code),
+            operation: None,
+            path: None,
+            errno: None,
+        }
+    }
+}
+
+impl PartialEq<shm::ErrorCode> for Error {
+    fn eq(&self, code: &shm::ErrorCode) -> bool {
+        self.code() == Some(code)
+    }
+}
+
+impl PartialEq<Error> for shm::ErrorCode {
+    fn eq(&self, error: &Error) -> bool {
+        error == self
+    }
+}
+
+impl PartialEq<condvar::ErrorCode> for Error {
+    fn eq(&self, code: &condvar::ErrorCode) -> bool {
+        matches!(&self.kind, ErrorKind::Condvar(kind) if kind == code)
+    }
+}
+
+impl PartialEq<Error> for condvar::ErrorCode {
+    fn eq(&self, error: &Error) -> bool {
+        error == self
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::Shm(code) => code.fmt(f),
+            ErrorKind::Condvar(code) => code.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.operation, &self.path) {
+            (Some(operation), Some(path)) => write!(f, "{operation} {path} failed: ")?,
+            (Some(operation), None) => write!(f, "{operation} failed: ")?,
+            (None, Some(path)) => write!(f, "{path}: ")?,
+            (None, None) => {}
+        }
+        self.kind.fmt(f)?;
+        match self.errno {
+            Some(errno) => write!(f, " ({errno})"),
+            None => Ok(()),
+        }
+    }
+}
+
+// The errno is part of the message: it is not reported again as the source of the error, which
+// error chains (e.g. anyhow) would print twice.
+impl std::error::Error for Error {}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        use shm::ErrorCode;
+        use std::io::ErrorKind as IoErrorKind;
+
+        let kind = match (error.errno, &error.kind) {
+            (Some(errno), _) => std::io::Error::from_raw_os_error(errno as i32).kind(),
+            (None, ErrorKind::Shm(code)) => match code {
+                ErrorCode::ShmPathDoesNotExist => IoErrorKind::NotFound,
+                ErrorCode::ShmPathAlreadyExists => IoErrorKind::AlreadyExists,
+                ErrorCode::ShmPathAccessDenied => IoErrorKind::PermissionDenied,
+                ErrorCode::WaitTimedOut => IoErrorKind::TimedOut,
+                ErrorCode::PeerDisconnected => IoErrorKind::ConnectionReset,
+                ErrorCode::OutOfMemory => IoErrorKind::OutOfMemory,
+                ErrorCode::SizeMismatch { .. }
+                | ErrorCode::HeaderMissing
+                | ErrorCode::InvalidHeader
+                | ErrorCode::HeaderVersionMismatch { .. }
+                | ErrorCode::PayloadOffsetMismatch { .. }
+                | ErrorCode::LayoutMismatch { .. } => IoErrorKind::InvalidData,
+                ErrorCode::ViewOutOfBounds { .. } | ErrorCode::ViewMisaligned { .. } => {
+                    IoErrorKind::InvalidInput
+                }
+                _ => IoErrorKind::Other,
+            },
+            (None, ErrorKind::Condvar(condvar::ErrorCode::WaitInterrupted)) => {
+                IoErrorKind::Interrupted
+            }
+            (None, ErrorKind::Condvar(_)) => IoErrorKind::Other,
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use crate::shm::ErrorCode;
+
+    use super::Error;
+
+    #[test]
+    fn display_describes_the_operation_path_and_errno() {
+        let error = Error::from_errno(ErrorCode::ShmPathAccessDenied, "shm_open", Errno::EACCES)
+            .with_path("log");
+
+        assert_eq!(
+            "shm_open log failed: access to the shared memory object was denied (EACCES: Permission denied)",
+            error.to_string()
+        );
+        assert_eq!(
+            "the shared memory object was not initialized in time",
+            Error::from(ErrorCode::WaitTimedOut).to_string()
+        );
+        assert!(std::error::Error::source(&error).is_none());
+    }
+
+    #[test]
+    fn io_errors_keep_the_kind_of_the_errno_and_the_error() {
+        let error = Error::from_errno(ErrorCode::ShmPathDoesNotExist, "shm_open", Errno::ENOENT);
+
+        let io_error = std::io::Error::from(error.clone());
+
+        assert_eq!(std::io::ErrorKind::NotFound, io_error.kind());
+        assert_eq!(
+            Some(&error),
+            io_error
+                .get_ref()
+                .and_then(|inner| inner.downcast_ref::<Error>())
+        );
+    }
+
+    #[test]
+    fn io_errors_are_mapped_from_codes_without_errno() {
+        let io_error = std::io::Error::from(Error::from(ErrorCode::LayoutMismatch {
+            expected: 1,
+            actual: 2,
+        }));
+
+        assert_eq!(std::io::ErrorKind::InvalidData, io_error.kind());
+    }
+}
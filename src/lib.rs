@@ -1,4 +1,7 @@
 #![cfg(unix)]
 
 pub mod condvar;
+pub mod error;
 pub mod shm;
+
+pub use error::Error;
@@ -1,26 +1,157 @@
+mod advice;
+mod collection;
+mod directory;
+mod fd;
+mod guard;
+mod header;
+mod heap;
+mod hugepage;
+mod list;
+mod memlock;
+mod numa;
+mod owner;
+mod pointer;
+mod protection;
+mod seal;
+mod teardown;
+mod typed;
+mod view;
+mod wait;
+
+use std::mem::ManuallyDrop;
 use std::num::NonZero;
-use std::os::fd::AsRawFd;
-use std::path::Path;
-use std::ptr::NonNull;
+use std::ops::{Deref, Range};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::ptr::{self, NonNull};
+use std::time::{Duration, Instant, SystemTime};
 
 use nix::errno::Errno;
-use nix::fcntl::OFlag;
-use nix::sys::mman::{mmap, munmap, shm_open, shm_unlink, MapFlags, ProtFlags};
-use nix::sys::stat::Mode;
-use nix::unistd::{close, ftruncate};
+use nix::fcntl::{open, readlink, OFlag};
+use nix::sys::memfd::{memfd_create, MFdFlags};
+use nix::sys::mman::{
+    madvise, mmap, mremap, shm_open, shm_unlink, MRemapFlags, MapFlags, ProtFlags,
+};
+use nix::sys::stat::{fchmod, fstat, Mode};
+use nix::unistd::{fchown, ftruncate, unlink, Gid};
 
 use libc::{c_void, off_t};
 
+use crate::Error;
+
+pub use advice::Advice;
+pub use collection::{ShmHashMap, ShmHashMapIter, ShmHashMapRef, ShmString, ShmVec, ShmVecRef};
+pub use directory::{ShmLayout, MAX_NAME_LEN};
+pub use fd::{recv_fd, send_fd};
+use header::Header;
+pub use header::{HEADER_SIZE, HEADER_VERSION};
+pub use heap::ShmHeap;
+pub use hugepage::HugePageSize;
+use hugepage::HugePages;
+pub use list::{list, list_in, HeaderInfo, SegmentInfo};
+pub use nix::fcntl::SealFlag;
+pub use numa::NumaPolicy;
+pub use pointer::{RelPtr, ShmPtr};
+pub use protection::Protection;
+use teardown::report_drop_error;
+pub use teardown::{set_drop_error_hook, DropErrorHook};
+pub use typed::Shm;
+pub use view::ShmSafe;
+
 ///
 /// ShmDefinition describes a shared memory object through its path and its allocated size.
 ///
-#[derive(Debug)]
+/// Definitions are built with [new](ShmDefinition::new) and the builder methods setting their
+/// [options](ShmOptions). Struct literals need the options as well, defaulted with
+/// `..Default::default()`:
+///
+/// ```
+/// use rshm::shm::ShmDefinition;
+///
+/// let definition = ShmDefinition {
+///     path: "example_literal".to_string(),
+///     size: std::num::NonZero::new(1024).unwrap(),
+///     ..Default::default()
+/// };
+/// let owned_shm = definition.create().unwrap();
+/// assert_eq!(1024, owned_shm.definition.size.get());
+/// ```
+///
+#[derive(Debug, Clone)]
 pub struct ShmDefinition {
     /// The path at which the shared memory file descriptor will be open
     /// (typially /dev/shm/..., /dev/hugepages/...)
     pub path: String,
     /// The size of the memory to allocate for this shared memory block.
     pub size: NonZero<usize>,
+    /// How the object is created and mapped, set with the builder methods of the definition.
+    pub options: ShmOptions,
+}
+
+///
+/// The options of a [ShmDefinition] beyond its path and size, set with its builder methods
+/// (e.g. [with_huge_pages](ShmDefinition::with_huge_pages)). The default options describe a
+/// plain object created with shm_open.
+///
+#[derive(Debug, Clone)]
+pub struct ShmOptions {
+    /// Huge pages backing the shared memory object, if any.
+    huge_pages: Option<HugePages>,
+    /// Whether the shared memory object is created with memfd_create rather than shm_open.
+    memfd: bool,
+    /// Whether open maps the actual size of the object rather than checking it against `size`.
+    discover_size: bool,
+    /// Whether the shared memory object starts with a header.
+    header: bool,
+    /// The fingerprint of the layout of the memory following the header.
+    fingerprint: Option<u64>,
+    /// The permissions of the created object, user read and write when not set.
+    mode: Option<Mode>,
+    /// The group owning the created object, the process' group when not set.
+    group: Option<Gid>,
+    /// Flags added to MAP_SHARED when mapping the object.
+    map_flags: MapFlags,
+    /// Hints given with madvise once the object is mapped.
+    advice: Vec<Advice>,
+    /// The NUMA nodes the memory of the object is allocated on, the process' policy when not set.
+    numa_policy: Option<NumaPolicy>,
+    /// The seals the object must carry to be opened.
+    required_seals: SealFlag,
+    /// Whether the mapping is surrounded by PROT_NONE guard pages.
+    guard_pages: bool,
+    /// Whether the memory of the created object starts with a directory of named objects.
+    directory: bool,
+}
+
+///
+/// The default definition has an empty path and a size of 1 byte: it is meant to complete
+/// struct literals setting both.
+///
+impl Default for ShmDefinition {
+    fn default() -> Self {
+        ShmDefinition::new(String::new(), NonZero::<usize>::MIN)
+    }
+}
+
+impl Default for ShmOptions {
+    fn default() -> Self {
+        ShmOptions {
+            huge_pages: None,
+            memfd: false,
+            discover_size: false,
+            header: false,
+            fingerprint: None,
+            mode: None,
+            group: None,
+            map_flags: MapFlags::empty(),
+            advice: Vec::new(),
+            numa_policy: None,
+            required_seals: SealFlag::empty(),
+            guard_pages: false,
+            directory: false,
+        }
+    }
 }
 
 ///
@@ -29,7 +160,7 @@ pub struct ShmDefinition {
 /// These codes are mapped from the libc reported error codes. The same integer code
 /// may be mapped to multiple ErrorCode values to reflect the context of the error.
 ///
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ErrorCode {
     /// The process could not access the given path to open a shared memory object.
     ShmPathAccessDenied,
@@ -61,124 +192,1553 @@ pub enum ErrorCode {
     CloseInterrupted,
     /// Attempt to unlink a file that does not exist.
     UnlinkingANonExistentFile,
+    /// The kernel does not support the requested huge page size.
+    HugePageSizeUnsupported,
+    /// No huge pages of the requested size are reserved (see /proc/sys/vm/nr_hugepages),
+    /// or not enough of them are free to back the mapping.
+    NoHugePagesReserved,
+    /// No hugetlbfs mount serves the requested huge page size.
+    HugeTlbFsNotMounted,
+    /// Anonymous (memfd) shared memory objects have no path and cannot be opened.
+    AnonymousShmCannotBeOpened,
+    /// The peer of the unix stream closed the connection.
+    PeerDisconnected,
+    /// The message received from the unix stream did not carry a file descriptor.
+    NoFileDescriptorReceived,
+    /// The message received from the unix stream carried more ancillary data than expected
+    /// (e.g. several file descriptors), which the kernel truncated.
+    ControlMessageTruncated,
+    /// The size of the existing shared memory object differs from the expected size.
+    SizeMismatch {
+        /// The size given by the definition.
+        expected: usize,
+        /// The size of the shared memory object.
+        actual: usize,
+    },
+    /// The operation requires a shared memory object with a header.
+    HeaderMissing,
+    /// The header of the shared memory object is not a valid rshm header.
+    InvalidHeader,
+    /// The size of the shared memory object leaves no room after its header.
+    ShmTooSmallForHeader,
+    /// The shared memory object was not created or initialized before the timeout.
+    WaitTimedOut,
+    /// The header was written with another version of its format.
+    HeaderVersionMismatch { expected: u32, actual: u32 },
+    /// The memory following the header does not start at the expected offset.
+    PayloadOffsetMismatch { expected: usize, actual: usize },
+    /// The memory following the header holds another layout than expected.
+    LayoutMismatch { expected: u64, actual: u64 },
+    /// The shared memory object is owned by the process of the given id.
+    AlreadyOwned { pid: i32 },
+    /// The shared memory object has no owner: it was given up to be adopted.
+    Unowned,
+    /// The permissions of the created object could not be changed.
+    ChangeModeRefused,
+    /// The group of the created object could not be changed (the process needs to belong to
+    /// the group, or CAP_CHOWN).
+    ChangeGroupRefused,
+    /// The mapping could not be locked in memory: it does not fit in the RLIMIT_MEMLOCK of the
+    /// process (given in bytes, None when unlimited) or the process lacks CAP_IPC_LOCK.
+    LockRefused { memlock_limit: Option<u64> },
+    /// The kernel refused the given advice on the mapping.
+    AdviceRefused(Advice),
+    /// The object cannot be sealed: it was not created [with_memfd](ShmDefinition::with_memfd),
+    /// it is sealed with F_SEAL_SEAL, or another process maps it for writing.
+    SealingRefused,
+    /// The object does not carry all the seals required by the definition.
+    SealsMissing {
+        required: SealFlag,
+        actual: SealFlag,
+    },
+    /// The kernel refused the given NUMA policy (e.g. it names nodes that are not online).
+    NumaPolicyRefused(NumaPolicy),
+    /// A view does not fit in the mapped memory object (it ends at the given offset).
+    ViewOutOfBounds { end: usize, len: usize },
+    /// A view at the given offset does not have the alignment required by its type.
+    ViewMisaligned { offset: usize, align: usize },
+    /// The heap has no free block for an allocation of the given number of bytes.
+    HeapExhausted { size: usize },
+    /// The memory does not hold a heap formatted by [ShmHeap::create], or its metadata is corrupted.
+    InvalidHeap,
+    /// No allocated block of the heap starts at the given offset (e.g. it was already freed).
+    InvalidAllocation { offset: usize },
+    /// The heap cannot allocate values aligned on the given number of bytes (more than 16).
+    AlignmentUnsupported { align: usize },
+    /// The bytes of a string are not UTF-8 past the given number of bytes.
+    InvalidUtf8 { valid_up_to: usize },
+    /// No object is registered with the given name in the directory of the shared memory object.
+    ObjectNotFound(String),
+    /// An object is already registered with the given name.
+    ObjectAlreadyRegistered(String),
+    /// The name is empty, holds zeroes or is longer than [MAX_NAME_LEN] bytes.
+    ObjectNameInvalid(String),
+    /// The directory holds as many objects as it can.
+    DirectoryFull,
+    /// The shared memory object was not created [with_directory](ShmDefinition::with_directory).
+    DirectoryMissing,
+    /// A protected range does not start or end on a page of the given size.
+    ProtectionMisaligned { offset: usize, page_size: usize },
+    /// The given protection was refused (e.g. writing to an object opened for reading only).
+    ProtectionRefused(Protection),
     /// An unmapped error was reported with the given return code.
     Unknown(Errno),
 }
 
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorCode::ShmPathAccessDenied => {
+                write!(f, "access to the shared memory object was denied")
+            }
+            ErrorCode::ShmPathAlreadyExists => {
+                write!(f, "the shared memory object already exists")
+            }
+            ErrorCode::ShmPathInvalid => {
+                write!(f, "the path of the shared memory object is invalid")
+            }
+            ErrorCode::ProcessTooManyOpenFD => {
+                write!(f, "the process has too many open file descriptors")
+            }
+            ErrorCode::ShmPathTooLong => {
+                write!(f, "the path of the shared memory object is too long")
+            }
+            ErrorCode::SystemTooManyOpenFiles => write!(f, "the system has too many open files"),
+            ErrorCode::ShmPathDoesNotExist => write!(f, "the shared memory object does not exist"),
+            ErrorCode::TruncateInterrupted => write!(f, "the truncation was interrupted"),
+            ErrorCode::InvalidTruncationSize => write!(f, "the size is too small or too large"),
+            ErrorCode::InvalidMMapArguments => {
+                write!(f, "the arguments of the mapping are invalid")
+            }
+            ErrorCode::OutOfMemory => write!(f, "not enough memory"),
+            ErrorCode::MissingPermission => write!(f, "the mapping is not permitted"),
+            ErrorCode::CloseIOError => write!(f, "an IO error occurred when closing"),
+            ErrorCode::CloseInterrupted => write!(f, "closing was interrupted"),
+            ErrorCode::UnlinkingANonExistentFile => {
+                write!(f, "the shared memory object was already unlinked")
+            }
+            ErrorCode::HugePageSizeUnsupported => {
+                write!(f, "the huge page size is not supported by the kernel")
+            }
+            ErrorCode::NoHugePagesReserved => write!(f, "not enough huge pages are reserved"),
+            ErrorCode::HugeTlbFsNotMounted => {
+                write!(f, "no hugetlbfs mount serves the huge page size")
+            }
+            ErrorCode::AnonymousShmCannotBeOpened => {
+                write!(f, "anonymous shared memory objects cannot be opened")
+            }
+            ErrorCode::PeerDisconnected => write!(f, "the peer disconnected"),
+            ErrorCode::NoFileDescriptorReceived => write!(f, "no file descriptor was received"),
+            ErrorCode::ControlMessageTruncated => {
+                write!(f, "the ancillary data of the message was truncated")
+            }
+            ErrorCode::SizeMismatch { expected, actual } => {
+                write!(f, "the size is {actual} bytes instead of {expected}")
+            }
+            ErrorCode::HeaderMissing => write!(f, "the shared memory object needs a header"),
+            ErrorCode::InvalidHeader => write!(f, "the header is not a valid rshm header"),
+            ErrorCode::ShmTooSmallForHeader => {
+                write!(f, "the shared memory object is too small for a header")
+            }
+            ErrorCode::WaitTimedOut => {
+                write!(f, "the shared memory object was not initialized in time")
+            }
+            ErrorCode::HeaderVersionMismatch { expected, actual } => {
+                write!(f, "the header version is {actual} instead of {expected}")
+            }
+            ErrorCode::PayloadOffsetMismatch { expected, actual } => {
+                write!(f, "the payload starts at {actual} instead of {expected}")
+            }
+            ErrorCode::LayoutMismatch { expected, actual } => write!(
+                f,
+                "the layout fingerprint is {actual:#x} instead of {expected:#x}"
+            ),
+            ErrorCode::AlreadyOwned { pid } => {
+                write!(f, "the shared memory object is owned by process {pid}")
+            }
+            ErrorCode::Unowned => write!(f, "the shared memory object has no owner"),
+            ErrorCode::ChangeModeRefused => write!(f, "the permissions could not be changed"),
+            ErrorCode::ChangeGroupRefused => write!(f, "the group could not be changed"),
+            ErrorCode::LockRefused {
+                memlock_limit: Some(limit),
+            } => write!(
+                f,
+                "the mapping could not be locked in memory (RLIMIT_MEMLOCK is {limit} bytes)"
+            ),
+            ErrorCode::LockRefused {
+                memlock_limit: None,
+            } => write!(
+                f,
+                "the mapping could not be locked in memory (RLIMIT_MEMLOCK is unlimited)"
+            ),
+            ErrorCode::AdviceRefused(advice) => write!(f, "the advice {advice:?} was refused"),
+            ErrorCode::SealingRefused => write!(f, "the shared memory object cannot be sealed"),
+            ErrorCode::SealsMissing { required, actual } => write!(
+                f,
+                "the shared memory object is sealed with {actual:?} instead of {required:?}"
+            ),
+            ErrorCode::NumaPolicyRefused(policy) => {
+                write!(f, "the NUMA policy {policy:?} was refused")
+            }
+            ErrorCode::ViewOutOfBounds { end, len } => {
+                write!(f, "the view ends at {end}, past the {len} mapped bytes")
+            }
+            ErrorCode::ViewMisaligned { offset, align } => {
+                write!(f, "the view at {offset} is not aligned on {align} bytes")
+            }
+            ErrorCode::HeapExhausted { size } => {
+                write!(f, "the heap has no room for {size} bytes")
+            }
+            ErrorCode::InvalidHeap => write!(f, "the memory does not hold a valid heap"),
+            ErrorCode::InvalidAllocation { offset } => {
+                write!(f, "no allocated block of the heap starts at {offset}")
+            }
+            ErrorCode::AlignmentUnsupported { align } => {
+                write!(f, "the heap cannot align values on {align} bytes")
+            }
+            ErrorCode::InvalidUtf8 { valid_up_to } => {
+                write!(f, "the string is not UTF-8 past {valid_up_to} bytes")
+            }
+            ErrorCode::ObjectNotFound(name) => write!(f, "no object is named {name:?}"),
+            ErrorCode::ObjectAlreadyRegistered(name) => {
+                write!(f, "an object is already named {name:?}")
+            }
+            ErrorCode::ObjectNameInvalid(name) => write!(f, "the object name {name:?} is invalid"),
+            ErrorCode::DirectoryFull => write!(f, "the directory of objects is full"),
+            ErrorCode::DirectoryMissing => {
+                write!(f, "the shared memory object has no directory of objects")
+            }
+            ErrorCode::ProtectionMisaligned { offset, page_size } => {
+                write!(
+                    f,
+                    "the protected range at {offset} is not on a {page_size} bytes page"
+                )
+            }
+            ErrorCode::ProtectionRefused(protection) => {
+                write!(f, "the protection {protection:?} was refused")
+            }
+            ErrorCode::Unknown(errno) => write!(f, "unexpected error {errno}"),
+        }
+    }
+}
+
 impl ShmDefinition {
+    ///
+    /// Describes a shared memory object at the given path with the given size.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_new", std::num::NonZero::new(1024).unwrap());
+    /// assert_eq!("example_new", definition.path);
+    /// assert_eq!(1024, definition.size.get());
+    /// ```
+    ///
+    pub fn new(path: impl Into<String>, size: NonZero<usize>) -> Self {
+        ShmDefinition {
+            path: path.into(),
+            size,
+            options: ShmOptions::default(),
+        }
+    }
+
+    ///
+    /// Backs the shared memory object with huge pages of the given size.
+    ///
+    /// The object is created in the hugetlbfs mount serving that page size (as listed in
+    /// /proc/mounts) and mapped with MAP_HUGETLB. The size of the definition is rounded up
+    /// to a multiple of the page size.
+    ///
+    /// ```
+    /// use rshm::shm::{HugePageSize, ShmDefinition};
+    ///
+    /// let definition = ShmDefinition::new("example_huge", std::num::NonZero::new(1024).unwrap())
+    ///     .with_huge_pages(HugePageSize::TwoMiB);
+    /// assert_eq!(2 * 1024 * 1024, definition.size.get());
+    /// ```
+    ///
+    pub fn with_huge_pages(self, page_size: HugePageSize) -> Self {
+        self.with_huge_pages_mount(page_size, None)
+    }
+
+    ///
+    /// Backs the shared memory object with huge pages from the given hugetlbfs mount point.
+    ///
+    /// This is useful when several hugetlbfs mounts serve the same page size.
+    ///
+    pub fn with_huge_pages_at(self, page_size: HugePageSize, mount: impl Into<PathBuf>) -> Self {
+        self.with_huge_pages_mount(page_size, Some(mount.into()))
+    }
+
+    fn with_huge_pages_mount(self, page_size: HugePageSize, mount: Option<PathBuf>) -> Self {
+        ShmDefinition {
+            size: page_size.round_up(self.size),
+            options: ShmOptions {
+                huge_pages: Some(HugePages {
+                    size: page_size,
+                    mount,
+                }),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Creates the shared memory object with memfd_create rather than as a named object.
+    ///
+    /// The object cannot be opened by name: its file descriptor has to be passed to
+    /// other processes (see [OwnedShmMap::send] and [ShmMap::receive]). It disappears
+    /// when the last mapping and file descriptor referring to it are closed, even when
+    /// processes crash. The path of the definition is only used as a label
+    /// (shown in `/proc/<pid>/fd`).
+    ///
+    /// ```
+    /// use std::os::unix::net::UnixStream;
+    /// use rshm::shm::{ShmDefinition, ShmMap};
+    ///
+    /// let definition = ShmDefinition::new("example_memfd", std::num::NonZero::new(1024).unwrap())
+    ///     .with_memfd();
+    /// let owned_shm = definition.create().unwrap();
+    /// assert!(std::fs::metadata("/dev/shm/example_memfd").is_err());
+    ///
+    /// let (sender, receiver) = UnixStream::pair().unwrap();
+    /// owned_shm.send(&sender).unwrap();
+    /// let shm = ShmMap::receive(&receiver).unwrap();
+    /// unsafe { (owned_shm.head() as *mut u8).write(8) };
+    /// assert_eq!(8, unsafe { shm.head().read() });
+    /// assert_eq!(1024, shm.definition.size.get());
+    /// ```
+    ///
+    pub fn with_memfd(self) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                memfd: true,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Requires the opened object to carry the given seals (see [OwnedShmMap::seal]), e.g.
+    /// F_SEAL_WRITE to trust that its content cannot change anymore. Objects missing one of
+    /// them are not mapped and a [SealsMissing](ErrorCode::SealsMissing) error is returned.
+    ///
+    /// ```
+    /// use std::os::unix::net::UnixStream;
+    /// use rshm::shm::{recv_fd, SealFlag, ShmDefinition};
+    ///
+    /// let seals = SealFlag::F_SEAL_WRITE | SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_GROW;
+    /// let definition = ShmDefinition::new("example_seal", std::num::NonZero::new(1024).unwrap())
+    ///     .with_memfd();
+    /// let mut owned_shm = definition.create().unwrap();
+    /// owned_shm.as_mut_slice()[0] = 8;
+    /// let sealed_shm = owned_shm.seal(seals).unwrap();
+    ///
+    /// let (sender, receiver) = UnixStream::pair().unwrap();
+    /// rshm::shm::send_fd(&sender, std::os::fd::AsFd::as_fd(&sealed_shm)).unwrap();
+    /// let definition = ShmDefinition::new("example_seal", std::num::NonZero::new(1024).unwrap())
+    ///     .with_memfd()
+    ///     .require_seals(seals);
+    /// let shm = definition.open_fd_read_only(recv_fd(&receiver).unwrap()).unwrap();
+    /// assert_eq!(8, shm.as_slice()[0]);
+    /// ```
+    ///
+    pub fn require_seals(self, seals: SealFlag) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                required_seals: self.options.required_seals | seals,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Opens the shared memory object with its actual size rather than the size of this
+    /// definition. The size is read with fstat and replaces the size of the definition.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition_owned = ShmDefinition::new("example_sized", std::num::NonZero::new(4096).unwrap());
+    /// let definition = ShmDefinition::new("example_sized", std::num::NonZero::<usize>::MIN)
+    ///     .with_discovered_size();
+    /// let _owned_shm = definition_owned.create().unwrap();
+    /// let shm = definition.open().unwrap();
+    /// assert_eq!(4096, shm.definition.size.get());
+    /// ```
+    ///
+    pub fn with_discovered_size(self) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                discover_size: true,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Reserves a header of [HEADER_SIZE] bytes at the start of the shared memory object.
+    ///
+    /// The header is written by [create](ShmDefinition::create) and lets other processes
+    /// wait for the owner to initialize the object (see [open_wait](ShmDefinition::open_wait)).
+    /// Mapped objects then start their memory after the header: [head](ShmMapping::head) points
+    /// past it and [len](ShmMapping::len) excludes it. The size of the definition includes the header.
+    ///
+    /// The header describes the object (format version, total size, offset of the memory
+    /// following it, layout fingerprint and creation time), which is checked when it is opened.
+    ///
+    pub fn with_header(self) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                header: true,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Records the layout of T in the header, so that processes opening the object with
+    /// another layout get a [LayoutMismatch](ErrorCode::LayoutMismatch) error.
+    ///
+    /// The fingerprint is computed from the name, size and alignment of T. It requires a
+    /// [header](ShmDefinition::with_header).
+    ///
+    /// ```
+    /// use rshm::shm::{ErrorCode, ShmDefinition};
+    ///
+    /// let definition_owned = ShmDefinition::new("example_layout", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header()
+    ///     .with_layout::<[u64; 4]>();
+    /// let definition = ShmDefinition::new("example_layout", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header()
+    ///     .with_layout::<[u32; 4]>();
+    /// let _owned_shm = definition_owned.create().unwrap();
+    /// let error = definition.open().unwrap_err();
+    /// assert!(matches!(error.code(), Some(ErrorCode::LayoutMismatch { .. })));
+    /// ```
+    ///
+    pub fn with_layout<T>(self) -> Self {
+        self.with_fingerprint(header::layout_fingerprint::<T>())
+    }
+
+    ///
+    /// Records the given fingerprint in the header, e.g. a version of the format of the memory
+    /// following the header (see [with_layout](ShmDefinition::with_layout)).
+    ///
+    pub fn with_fingerprint(self, fingerprint: u64) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                fingerprint: Some(fingerprint),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Sets the permissions of the created object (e.g. 0o640 to let the group read it).
+    ///
+    /// The permissions are set with fchmod once the object is created, so they do not
+    /// depend on the process' umask.
+    ///
+    /// ```
+    /// use std::os::unix::fs::PermissionsExt;
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_mode", std::num::NonZero::new(1024).unwrap())
+    ///     .with_mode(0o640);
+    /// let _shm = definition.create().unwrap();
+    /// let metadata = std::fs::metadata("/dev/shm/example_mode").unwrap();
+    /// assert_eq!(0o640, metadata.permissions().mode() & 0o777);
+    /// ```
+    ///
+    pub fn with_mode(self, mode: u32) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                mode: Some(Mode::from_bits_truncate(mode as libc::mode_t)),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Sets the group owning the created object, so that processes of other users
+    /// in that group can open it (given a [mode](ShmDefinition::with_mode) granting access).
+    ///
+    pub fn with_group(self, gid: u32) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                group: Some(Gid::from_raw(gid)),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Prefaults the pages of the mapping (MAP_POPULATE), so that first accesses do not fault.
+    ///
+    pub fn with_populate(self) -> Self {
+        self.with_map_flags(MapFlags::MAP_POPULATE)
+    }
+
+    ///
+    /// Does not reserve swap space for the mapping (MAP_NORESERVE).
+    ///
+    pub fn with_no_reserve(self) -> Self {
+        self.with_map_flags(MapFlags::MAP_NORESERVE)
+    }
+
+    ///
+    /// Locks the pages of the mapping in memory (MAP_LOCKED), within the limit of RLIMIT_MEMLOCK.
+    ///
+    pub fn with_locked(self) -> Self {
+        self.with_map_flags(MapFlags::MAP_LOCKED)
+    }
+
+    fn with_map_flags(self, map_flags: MapFlags) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                map_flags: self.options.map_flags | map_flags,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Gives the kernel a hint about the use of the mapping, with madvise once it is mapped.
+    /// Several hints can be given by calling this method several times.
+    ///
+    /// ```
+    /// use rshm::shm::{Advice, ShmDefinition};
+    ///
+    /// let definition = ShmDefinition::new("example_advice", std::num::NonZero::new(1024).unwrap())
+    ///     .with_advice(Advice::DontDump)
+    ///     .with_advice(Advice::WillNeed);
+    /// let _shm = definition.create().unwrap();
+    /// ```
+    ///
+    pub fn with_advice(mut self, advice: Advice) -> Self {
+        self.options.advice.push(advice);
+        self
+    }
+
+    ///
+    /// Allocates the memory of the object on the NUMA nodes of the given policy (with mbind),
+    /// e.g. the memory of the socket the producers and consumers are pinned to.
+    ///
+    /// The policy is applied to the mapping before its pages are first touched: pages
+    /// prefaulted [with_populate](ShmDefinition::with_populate) are faulted in once it is
+    /// applied. On machines with a single node, a policy naming other nodes is ignored.
+    ///
+    /// ```
+    /// use rshm::shm::{NumaPolicy, ShmDefinition};
+    ///
+    /// let definition = ShmDefinition::new("example_numa", std::num::NonZero::new(8192).unwrap())
+    ///     .with_numa_policy(NumaPolicy::Bind(vec![0]));
+    /// let owned_shm = definition.create().unwrap();
+    /// owned_shm.prefault();
+    /// assert!(owned_shm.page_nodes().unwrap().iter().all(|node| *node == Some(0)));
+    /// ```
+    ///
+    pub fn with_numa_policy(self, numa_policy: NumaPolicy) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                numa_policy: Some(numa_policy),
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Surrounds the mapping with PROT_NONE guard pages, so that reading or writing past either
+    /// end of the object faults immediately (SIGSEGV) instead of reaching unrelated memory.
+    ///
+    /// Each guard is a page (a huge page for objects backed by huge pages), reserved in the
+    /// address space of the process without using memory. The mapping is laid out as:
+    ///
+    /// ```text
+    /// | guard page | header (if any) | memory from head()    | guard page |
+    /// ^ base - page ^ base           ^ base + HEADER_SIZE    ^ base + size rounded up to pages
+    /// ```
+    ///
+    /// Accesses past the end of an object whose size is not a multiple of the page size only
+    /// fault past the end of its last page. Growing the object moves the mapping to a new
+    /// reservation with its guard pages.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_guard", std::num::NonZero::new(8192).unwrap())
+    ///     .with_guard_pages();
+    /// let owned_shm = definition.create().unwrap();
+    /// assert_eq!(0, owned_shm.head() as usize % 4096);
+    /// ```
+    ///
+    pub fn with_guard_pages(self) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                guard_pages: true,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
+    ///
+    /// Starts the memory of the created object with a directory of named objects, formatted by
+    /// [create](ShmDefinition::create) in the first 4096 bytes following
+    /// [head](ShmMapping::head) (see [OwnedShmMap::register]).
+    ///
+    pub fn with_directory(self) -> Self {
+        ShmDefinition {
+            options: ShmOptions {
+                directory: true,
+                ..self.options
+            },
+            ..self
+        }
+    }
+
     ///
     /// Create a shared memory object from this definition.
     /// The mapped object is owned and will be unlinked when the OwnerShmMap is dropped.
     /// ```
     /// use rshm::shm::ShmDefinition;
     ///
-    /// let definition = ShmDefinition {
-    ///     path: "test1".to_string(),
-    ///     size: std::num::NonZero::new(1024).unwrap(),
-    /// };
+    /// let definition = ShmDefinition::new("test1", std::num::NonZero::new(1024).unwrap());
     /// let _shm = definition.create().unwrap();
     /// let metadata = std::fs::metadata("/dev/shm/test1").unwrap();
     /// assert!(metadata.is_file());
     /// assert_eq!(1024, metadata.len());
     /// ```
     ///
-    pub fn create(self) -> Result<OwnedShmMap, ErrorCode> {
-        let path = self.path.as_str();
-        shm_open(
-            path,
-            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR, //create exclusively (error if collision) and write to allow resize
-            Mode::S_IRUSR | Mode::S_IWUSR,                  //Permission allow user+rw
-        )
-        .map_err(map_open_error)
-        .and_then(|fd| {
-            (&self)
-                .create_mmap(&fd, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)
-                .and_then(|p| {
-                    Ok(OwnedShmMap {
-                        definition: self,
-                        head: p,
+    pub fn create(self) -> Result<OwnedShmMap, Error> {
+        if let Some(huge_pages) = &self.options.huge_pages {
+            huge_pages
+                .check_reserved()
+                .map_err(|error| self.error(error))?;
+        }
+        if self.options.header && self.size.get() <= HEADER_SIZE {
+            return Err(self.error(ErrorCode::ShmTooSmallForHeader));
+        }
+        let len = self.size.get() - if self.options.header { HEADER_SIZE } else { 0 };
+        if self.options.directory && len < directory::DIRECTORY_SIZE {
+            return Err(self.error(ErrorCode::ViewOutOfBounds {
+                end: directory::DIRECTORY_SIZE,
+                len,
+            }));
+        }
+        self.check_fingerprint()?;
+        let directory = self.options.directory;
+        self.create_fd()
+            .and_then(|fd| {
+                self.create_mmap(&fd, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)
+                    .map(|p| {
+                        if self.options.header {
+                            unsafe { Header::at(p.as_ptr() as *const u8) }
+                                .write_created(self.size, self.options.fingerprint);
+                        }
+                        OwnedShmMap {
+                            mapping: ShmMapping::new(self, p, fd, true),
+                        }
                     })
-                })
-        })
+            })
+            .and_then(|mut owned_shm| {
+                if directory {
+                    directory::format(owned_shm.mapping.as_mut_slice())
+                        .map_err(|error| owned_shm.definition.error(error))?;
+                }
+                Ok(owned_shm)
+            })
+    }
+
+    ///
+    /// Creates a shared memory object from this definition, reclaiming the object left by a
+    /// dead owner if it exists (see [is_stale](ShmMapping::is_stale)).
+    ///
+    /// The owner is recorded in the header, which is required. The stale object is unlinked once
+    /// its ownership is taken over in its header, so that concurrent reclaimers never unlink
+    /// each other's objects: they fail with [AlreadyOwned](ErrorCode::AlreadyOwned) or
+    /// [ShmPathAlreadyExists](ErrorCode::ShmPathAlreadyExists) instead.
+    ///
+    pub fn create_or_reclaim(self) -> Result<OwnedShmMap, Error> {
+        if !self.options.header {
+            return Err(self.error(ErrorCode::HeaderMissing));
+        }
+        match self.clone().create() {
+            Err(error) if error == ErrorCode::ShmPathAlreadyExists => {
+                self.clone().unlink_stale()?;
+                self.create()
+            }
+            other => other,
+        }
+    }
+
+    ///
+    /// Unlinks the shared memory object of this definition if its owner is dead
+    /// (see [create_or_reclaim](ShmDefinition::create_or_reclaim)), otherwise returns an
+    /// [AlreadyOwned](ErrorCode::AlreadyOwned) error. Objects given up with
+    /// [into_unowned](OwnedShmMap::into_unowned) are not stale: they are left to be
+    /// [adopted](ShmMap::adopt) and an [Unowned](ErrorCode::Unowned) error is returned. The size
+    /// and layout of the object are not checked.
+    ///
+    pub fn unlink_stale(self) -> Result<(), Error> {
+        // The stale object may have been created with another size or layout.
+        let stale = ShmDefinition {
+            options: ShmOptions {
+                header: true,
+                discover_size: true,
+                fingerprint: None,
+                ..self.options
+            },
+            ..self
+        }
+        .open()?;
+        unsafe { Header::at(stale.base()) }
+            .reclaim_ownership()
+            .map_err(|error| stale.definition.error(error))?;
+        stale.unlink()
     }
 
     ///
     /// opens an existing shared memory object based on this definition.
     /// The mapped object is not considered owner and will not be unlinked when the ShmMap is dropped.
     ///
+    /// The object is never resized: its size must match the size of the definition, unless the
+    /// definition was built [with_discovered_size](ShmDefinition::with_discovered_size).
+    ///
     /// ```
     /// use rshm::shm::ShmDefinition;
     ///
-    /// let definition_owned = ShmDefinition {
-    ///     path: "example".to_string(),
-    ///     size: std::num::NonZero::new(1024).unwrap(),
-    /// };
-    /// let definition = ShmDefinition {
-    ///     path: "example".to_string(),
-    ///     size: std::num::NonZero::new(1024).unwrap(),
-    /// };
+    /// let definition_owned = ShmDefinition::new("example", std::num::NonZero::new(1024).unwrap());
+    /// let definition = ShmDefinition::new("example", std::num::NonZero::new(1024).unwrap());
     /// let owned_shm = definition_owned.create().unwrap();
     /// let shm = definition.open().unwrap();
     /// unsafe { (owned_shm.head() as *mut u8).write(8) };
-    /// assert_eq!(8, unsafe { (shm.head() as *const u8).read() });
+    /// assert_eq!(8, unsafe { shm.head().read() });
     /// ```
     ///
-    pub fn open(self) -> Result<ShmMap, ErrorCode> {
-        shm_open(
-            self.path.as_str(),
-            OFlag::O_RDWR,                 // write to allow resize
+    pub fn open(self) -> Result<ShmMap, Error> {
+        if self.options.memfd {
+            return Err(self.error(ErrorCode::AnonymousShmCannotBeOpened));
+        }
+        self.open_named(
+            OFlag::O_RDWR,                 // write to share updates
             Mode::S_IRUSR | Mode::S_IWUSR, //Permission allow user+rw
         )
-        .map_err(map_open_error)
-        .and_then(|fd| {
-            (&self)
-                .create_mmap(&fd, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)
-                .and_then(|p| {
-                    Ok(ShmMap {
-                        definition: self,
-                        head: p,
-                    })
-                })
-        })
+        .and_then(|fd| self.open_fd(fd))
     }
 
-    fn create_mmap<Fd: std::os::fd::AsFd>(
-        &self,
-        fd: &Fd,
-        flags: ProtFlags,
-    ) -> Result<NonNull<c_void>, ErrorCode> {
-        ftruncate(&fd, self.size.get() as off_t)
-            .map_err(map_truncate_error)
-            .and_then(|_| unsafe {
-                mmap(
-                    None,                 // Desired addr
-                    self.size,            // size of mapping
-                    flags,                // Permissions on pages
-                    MapFlags::MAP_SHARED, // What kind of mapping
-                    &fd,                  // fd
-                    0,                    // Offset into fd
-                )
-                .map_err(map_mmap_error)
+    ///
+    /// opens an existing shared memory object based on this definition, for reading only.
+    ///
+    /// The object is open with O_RDONLY and mapped with PROT_READ: a read permission on the
+    /// object is enough, and the mapped memory cannot be modified through this mapping.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition_owned = ShmDefinition::new("example_ro", std::num::NonZero::new(1024).unwrap());
+    /// let definition = ShmDefinition::new("example_ro", std::num::NonZero::new(1024).unwrap());
+    /// let owned_shm = definition_owned.create().unwrap();
+    /// let shm = definition.open_read_only().unwrap();
+    /// unsafe { (owned_shm.head() as *mut u8).write(8) };
+    /// assert_eq!(8, shm.as_slice()[0]);
+    /// ```
+    ///
+    pub fn open_read_only(self) -> Result<ReadOnlyShmMap, Error> {
+        if self.options.memfd {
+            return Err(self.error(ErrorCode::AnonymousShmCannotBeOpened));
+        }
+        self.open_named(
+            OFlag::O_RDONLY, // read only: the mapping cannot be written to
+            Mode::empty(),   // Permission is not used without O_CREAT
+        )
+        .and_then(|fd| self.open_fd_read_only(fd))
+    }
+
+    ///
+    /// opens a shared memory object based on this definition, waiting for its owner to create it
+    /// and to mark it as initialized (see [OwnedShmMap::mark_initialized]).
+    ///
+    /// The definition needs a [header](ShmDefinition::with_header), which carries the
+    /// initialization marker. The directory of the object (e.g. /dev/shm) is watched with
+    /// inotify until the object appears and is sized by its owner.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let consumer = std::thread::spawn(|| {
+    ///     let definition = ShmDefinition::new("example_wait", std::num::NonZero::new(8192).unwrap())
+    ///         .with_header();
+    ///     let shm = definition.open_wait(Duration::from_secs(5)).unwrap();
+    ///     unsafe { shm.head().read() }
+    /// });
+    /// let definition_owned = ShmDefinition::new("example_wait", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header();
+    /// let owned_shm = definition_owned.create().unwrap();
+    /// unsafe { (owned_shm.head() as *mut u8).write(8) };
+    /// owned_shm.mark_initialized().unwrap();
+    /// assert_eq!(8, consumer.join().unwrap());
+    /// ```
+    ///
+    pub fn open_wait(self, timeout: Duration) -> Result<ShmMap, Error> {
+        let deadline = Instant::now() + timeout;
+        self.check_waitable()?;
+        let fd = wait::wait_for_fd(&self, OFlag::O_RDWR, deadline)?;
+        let map = self.map_fd(fd)?;
+        let header = unsafe { Header::at(map.base()) };
+        header
+            .wait_initialized(deadline)
+            .and_then(|_| header.check(map.definition.size, map.definition.options.fingerprint))
+            .map_err(|error| map.definition.error(error))?;
+        Ok(map)
+    }
+
+    ///
+    /// opens a shared memory object based on this definition for reading only, waiting for its
+    /// owner to create it and to mark it as initialized (see [open_wait](ShmDefinition::open_wait)).
+    ///
+    pub fn open_read_only_wait(self, timeout: Duration) -> Result<ReadOnlyShmMap, Error> {
+        let deadline = Instant::now() + timeout;
+        self.check_waitable()?;
+        let fd = wait::wait_for_fd(&self, OFlag::O_RDONLY, deadline)?;
+        let map = self.map_fd_read_only(fd)?;
+        let header = unsafe { Header::at(map.base()) };
+        header
+            .wait_initialized(deadline)
+            .and_then(|_| header.check(map.definition.size, map.definition.options.fingerprint))
+            .map_err(|error| map.definition.error(error))?;
+        Ok(map)
+    }
+
+    fn check_waitable(&self) -> Result<(), Error> {
+        if self.options.memfd {
+            Err(self.error(ErrorCode::AnonymousShmCannotBeOpened))
+        } else if !self.options.header {
+            Err(self.error(ErrorCode::HeaderMissing))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_fingerprint(&self) -> Result<(), Error> {
+        if !self.options.header && self.options.fingerprint.is_some() {
+            Err(self.error(ErrorCode::HeaderMissing))
+        } else {
+            Ok(())
+        }
+    }
+
+    ///
+    /// Maps the shared memory object referred to by the given file descriptor,
+    /// typically received from its owner with [recv_fd].
+    ///
+    /// As with [open](ShmDefinition::open), the object is never resized and its header
+    /// (if any) is checked.
+    ///
+    pub fn open_fd(self, fd: OwnedFd) -> Result<ShmMap, Error> {
+        let map = self.map_fd(fd)?;
+        map.check_header()?;
+        Ok(map)
+    }
+
+    ///
+    /// Maps the shared memory object referred to by the given file descriptor for reading only
+    /// (see [open_fd](ShmDefinition::open_fd)), as needed for objects sealed with F_SEAL_WRITE.
+    ///
+    pub fn open_fd_read_only(self, fd: OwnedFd) -> Result<ReadOnlyShmMap, Error> {
+        let map = self.map_fd_read_only(fd)?;
+        map.check_header()?;
+        Ok(map)
+    }
+
+    fn map_fd(self, fd: OwnedFd) -> Result<ShmMap, Error> {
+        self.check_seals(&fd)?;
+        let definition = self.sized_to(&fd)?;
+        definition
+            .mmap(&fd, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)
+            .map(|p| ShmMap {
+                mapping: ShmMapping::new(definition, p, fd, true),
             })
-            .or_else(|err| {
-                let _close_result = close(fd.as_fd().as_raw_fd());
-                let _removal_result =
-                    std::fs::remove_file(Path::new(format!("/dev/shm/{}", self.path).as_str()));
-                Err(err)
+    }
+
+    fn map_fd_read_only(self, fd: OwnedFd) -> Result<ReadOnlyShmMap, Error> {
+        self.check_seals(&fd)?;
+        let definition = self.sized_to(&fd)?;
+        definition
+            .mmap(&fd, ProtFlags::PROT_READ)
+            .map(|p| ReadOnlyShmMap {
+                mapping: ShmMapping::new(definition, p, fd, false),
             })
     }
-}
 
-///
-/// A mapped shared memory object that was created by some other process.
-/// It will not be unlinked when dropped.
+    fn check_seals<Fd: AsFd>(&self, fd: &Fd) -> Result<(), Error> {
+        if self.options.required_seals.is_empty() {
+            Ok(())
+        } else {
+            seal::check_seals(fd, self.options.required_seals).map_err(|error| self.error(error))
+        }
+    }
+
+    ///
+    /// Checks the size of the object against this definition, or adopts it when
+    /// the size is discovered.
+    ///
+    fn sized_to<Fd: std::os::fd::AsFd>(self, fd: &Fd) -> Result<ShmDefinition, Error> {
+        self.check_fingerprint()?;
+        let actual = object_size(fd).map_err(|error| self.error(error))?;
+        match NonZero::new(actual) {
+            Some(size) if self.options.header && size.get() <= HEADER_SIZE => {
+                Err(self.error(ErrorCode::ShmTooSmallForHeader))
+            }
+            Some(size) if self.options.discover_size => Ok(ShmDefinition { size, ..self }),
+            Some(size) if size == self.size => Ok(self),
+            _ => Err(self.error(ErrorCode::SizeMismatch {
+                expected: self.size.get(),
+                actual,
+            })),
+        }
+    }
+
+    ///
+    /// The size of the header at the start of the shared memory object.
+    ///
+    fn header_size(&self) -> usize {
+        if self.options.header {
+            HEADER_SIZE
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// The size of the pages backing the shared memory object.
+    ///
+    fn page_size(&self) -> usize {
+        match &self.options.huge_pages {
+            None => memlock::base_page_size(),
+            Some(huge_pages) => huge_pages.size.bytes(),
+        }
+    }
+
+    /// The size of each guard surrounding the mapping, 0 without guard pages.
+    fn guard_size(&self) -> usize {
+        if self.options.guard_pages {
+            self.page_size()
+        } else {
+            0
+        }
+    }
+
+    ///
+    /// Records the path of this definition in the given error.
+    ///
+    fn error(&self, error: impl Into<Error>) -> Error {
+        error.into().with_path(&self.path)
+    }
+
+    ///
+    /// The directory holding the shared memory object.
+    ///
+    fn directory(&self) -> Result<PathBuf, Error> {
+        match &self.options.huge_pages {
+            None => Ok(PathBuf::from("/dev/shm")),
+            Some(huge_pages) => huge_pages
+                .file_path(&self.path)
+                .map(|path| path.parent().map(PathBuf::from).unwrap_or_default())
+                .map_err(|error| self.error(error)),
+        }
+    }
+
+    fn create_fd(&self) -> Result<OwnedFd, Error> {
+        if self.options.memfd {
+            let flags = match &self.options.huge_pages {
+                None => MFdFlags::MFD_CLOEXEC | MFdFlags::MFD_ALLOW_SEALING,
+                Some(huge_pages) => {
+                    MFdFlags::MFD_CLOEXEC
+                        | MFdFlags::MFD_ALLOW_SEALING
+                        | huge_pages.size.memfd_flags()
+                }
+            };
+            memfd_create(self.path.as_str(), flags)
+                .map_err(|errno| self.error(map_memfd_error(errno)))
+        } else {
+            self.open_named(
+                OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR, //create exclusively (error if collision) and write to allow resize
+                self.options.mode.unwrap_or(Mode::S_IRUSR | Mode::S_IWUSR), //Permission allow user+rw by default
+            )
+        }
+    }
+
+    fn open_named(&self, flags: OFlag, mode: Mode) -> Result<OwnedFd, Error> {
+        match &self.options.huge_pages {
+            None => shm_open(self.path.as_str(), flags, mode)
+                .map_err(|errno| self.error(map_open_error("shm_open", errno))),
+            Some(huge_pages) => huge_pages
+                .file_path(&self.path)
+                .and_then(|path| {
+                    open(&path, flags, mode).map_err(|errno| map_open_error("open", errno))
+                })
+                .map_err(|error| self.error(error)),
+        }
+    }
+
+    fn map_flags(&self) -> MapFlags {
+        // Pages are populated once the NUMA policy is applied.
+        let map_flags = match &self.options.numa_policy {
+            None => self.options.map_flags,
+            Some(_) => self.options.map_flags - MapFlags::MAP_POPULATE,
+        };
+        match &self.options.huge_pages {
+            None => MapFlags::MAP_SHARED | map_flags,
+            Some(huge_pages) => MapFlags::MAP_SHARED | map_flags | huge_pages.size.map_flags(),
+        }
+    }
+
+    fn create_mmap<Fd: std::os::fd::AsFd>(
+        &self,
+        fd: &Fd,
+        flags: ProtFlags,
+    ) -> Result<NonNull<c_void>, Error> {
+        self.set_permissions(fd)
+            .and_then(|_| {
+                ftruncate(fd, self.size.get() as off_t)
+                    .map_err(|errno| self.error(map_truncate_error(errno)))
+            })
+            .and_then(|_| self.mmap(fd, flags))
+            .inspect_err(|_| {
+                let _removal_result = self.unlink();
+            })
+    }
+
+    fn mmap<Fd: std::os::fd::AsFd>(
+        &self,
+        fd: &Fd,
+        flags: ProtFlags,
+    ) -> Result<NonNull<c_void>, Error> {
+        let (addr, map_flags) = if self.options.guard_pages {
+            let reserved = guard::reserve(self.size.get(), self.guard_size())
+                .map_err(|errno| self.error(map_mmap_error(errno)))?;
+            (
+                NonZero::new(reserved.as_ptr() as usize),
+                self.map_flags() | MapFlags::MAP_FIXED,
+            )
+        } else {
+            (None, self.map_flags())
+        };
+        unsafe {
+            mmap(
+                addr,      // Desired addr
+                self.size, // size of mapping
+                flags,     // Permissions on pages
+                map_flags, // What kind of mapping
+                fd,        // fd
+                0,         // Offset into fd
+            )
+        }
+        .inspect_err(|_| {
+            if let Some(reserved) = addr.and_then(|addr| NonNull::new(addr.get() as *mut c_void)) {
+                let _release_result = guard::release(reserved, self.size.get(), self.guard_size());
+            }
+        })
+        .map_err(|errno| match (&self.options.huge_pages, errno) {
+            (Some(_), Errno::ENOMEM) => {
+                Error::from_errno(ErrorCode::NoHugePagesReserved, "mmap", errno)
+            }
+            (_, Errno::EAGAIN) if self.options.map_flags.contains(MapFlags::MAP_LOCKED) => {
+                memlock::map_lock_error("mmap", errno)
+            }
+            (_, other) => map_mmap_error(other),
+        })
+        .map_err(|error| self.error(error))
+        .and_then(|p| {
+            self.place(p, flags)
+                .and_then(|_| self.advise(p))
+                .inspect_err(|_| {
+                    let _unmap_result = self.unmap(p);
+                })
+        })
+    }
+
+    ///
+    /// Applies the NUMA policy (if any) to the mapping, then populates it when requested.
+    ///
+    fn place(&self, head: NonNull<c_void>, flags: ProtFlags) -> Result<(), Error> {
+        if let Some(numa_policy) = &self.options.numa_policy {
+            numa::apply(numa_policy, head, self.size.get()).map_err(|error| self.error(error))?;
+            if self.options.map_flags.contains(MapFlags::MAP_POPULATE) {
+                let base = head.as_ptr() as *const u8;
+                if flags.contains(ProtFlags::PROT_WRITE) {
+                    memlock::prefault_write(base, self.size.get(), self.page_size());
+                } else {
+                    memlock::prefault_read(base, self.size.get(), self.page_size());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Maps the object at `head` with MAP_FIXED, replacing the pages mapped there.
+    ///
+    fn mmap_at<Fd: AsFd>(
+        &self,
+        fd: &Fd,
+        flags: ProtFlags,
+        head: NonNull<c_void>,
+    ) -> Result<(), Error> {
+        unsafe {
+            mmap(
+                NonZero::new(head.as_ptr() as usize),
+                self.size,
+                flags,
+                self.map_flags() | MapFlags::MAP_FIXED,
+                fd,
+                0,
+            )
+        }
+        .map_err(|errno| self.error(map_mmap_error(errno)))
+        .and_then(|p| self.place(p, flags).and_then(|_| self.advise(p)))
+        .map(|_| ())
+    }
+
+    ///
+    /// Unmaps the mapping at `head`, with its guard pages (if any).
+    ///
+    fn unmap(&self, head: NonNull<c_void>) -> Result<(), Error> {
+        guard::release(head, self.size.get(), self.guard_size())
+            .map_err(|errno| self.error(map_munmap_error(errno)))
+    }
+
+    fn advise(&self, head: NonNull<c_void>) -> Result<NonNull<c_void>, Error> {
+        self.options.advice.iter().try_for_each(|advice| {
+            unsafe { madvise(head, self.size.get(), advice.to_mmap_advise()) }.map_err(|errno| {
+                self.error(Error::from_errno(
+                    ErrorCode::AdviceRefused(*advice),
+                    "madvise",
+                    errno,
+                ))
+            })
+        })?;
+        Ok(head)
+    }
+
+    fn set_permissions<Fd: AsFd>(&self, fd: &Fd) -> Result<(), Error> {
+        if let Some(mode) = self.options.mode {
+            fchmod(fd, mode).map_err(|errno| {
+                self.error(Error::from_errno(
+                    ErrorCode::ChangeModeRefused,
+                    "fchmod",
+                    errno,
+                ))
+            })?;
+        }
+        if let Some(group) = self.options.group {
+            fchown(fd, None, Some(group)).map_err(|errno| {
+                self.error(Error::from_errno(
+                    ErrorCode::ChangeGroupRefused,
+                    "fchown",
+                    errno,
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn unlink(&self) -> Result<(), Error> {
+        match &self.options.huge_pages {
+            _ if self.options.memfd => Ok(()),
+            None => shm_unlink(self.path.as_str())
+                .map_err(|errno| self.error(map_unlink_error("shm_unlink", errno))),
+            Some(huge_pages) => huge_pages
+                .file_path(&self.path)
+                .and_then(|path| unlink(&path).map_err(|errno| map_unlink_error("unlink", errno)))
+                .map_err(|error| self.error(error)),
+        }
+    }
+}
+
+///
+/// The mapping of a shared memory object, holding the operations common to [ShmMap],
+/// [ReadOnlyShmMap] and [OwnedShmMap], which dereference to it. It is unmapped when dropped.
 ///
 #[derive(Debug)]
-pub struct ShmMap {
+pub struct ShmMapping {
     /// Definition of the shared memory object that is mapped
     pub definition: ShmDefinition,
     /// The pointer to the start of the memory mapped object
     head: NonNull<c_void>,
+    /// The file descriptor of the shared memory object
+    fd: OwnedFd,
+    /// The generation of the shared memory object when it was last mapped
+    generation: u64,
+    /// Whether the object is mapped for writing
+    writable: bool,
+}
+
+impl Drop for ShmMapping {
+    fn drop(&mut self) {
+        if let Err(error) = self.unmap() {
+            report_drop_error(&self.definition, &error);
+        }
+    }
+}
+
+impl ShmMapping {
+    fn new(definition: ShmDefinition, head: NonNull<c_void>, fd: OwnedFd, writable: bool) -> Self {
+        ShmMapping {
+            definition,
+            head,
+            fd,
+            generation: 0,
+            writable,
+        }
+    }
+
+    /// Unmaps the shared memory object, returning the errors that Drop only reports to the hook.
+    fn close(self) -> Result<(), Error> {
+        let mapping = ManuallyDrop::new(self);
+        let result = mapping.unmap();
+        // The mapping is released once: its remaining fields are dropped without calling Drop.
+        let (_definition, _fd) =
+            unsafe { (ptr::read(&mapping.definition), ptr::read(&mapping.fd)) };
+        result
+    }
+
+    fn unmap(&self) -> Result<(), Error> {
+        self.definition.unmap(self.head)
+    }
+
+    /// returns a pointer to the start of the mapped memory object (past its header, if any)
+    pub fn head(&self) -> *const u8 {
+        unsafe { self.base().add(self.definition.header_size()) }
+    }
+
+    /// returns the size of the mapped memory object available from [head](Self::head)
+    pub fn len(&self) -> usize {
+        self.definition.size.get() - self.definition.header_size()
+    }
+
+    /// returns true when no memory is available from [head](Self::head)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn base(&self) -> *const u8 {
+        self.head.as_ptr() as *const u8
+    }
+
+    /// returns the creation time of the mapped memory object, when it has a header
+    pub fn created(&self) -> Option<SystemTime> {
+        self.definition
+            .options
+            .header
+            .then(|| unsafe { Header::at(self.base()) }.created())
+    }
+
+    ///
+    /// Locks the mapped memory object in memory: its pages are never swapped out once faulted
+    /// in (see [prefault](Self::prefault)). Pages are locked as they are faulted in
+    /// (mlock2 with MLOCK_ONFAULT), all at once with mlock on kernels older than 4.4.
+    ///
+    /// Locking fails with [LockRefused](ErrorCode::LockRefused), which reports the
+    /// RLIMIT_MEMLOCK of the process, when the mapping does not fit in it.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_lock", std::num::NonZero::new(8192).unwrap());
+    /// let owned_shm = definition.create().unwrap();
+    /// owned_shm.lock().unwrap();
+    /// owned_shm.prefault();
+    /// ```
+    ///
+    pub fn lock(&self) -> Result<(), Error> {
+        memlock::lock(self.head, self.definition.size.get())
+            .map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Faults in every page of the mapped memory object, so that the first access to a page
+    /// does not pay for a page fault. Objects mapped for writing are faulted in for writing.
+    /// The content of the object is not modified.
+    ///
+    pub fn prefault(&self) {
+        let prefault = if self.writable {
+            memlock::prefault_write
+        } else {
+            memlock::prefault_read
+        };
+        prefault(
+            self.base(),
+            self.definition.size.get(),
+            self.definition.page_size(),
+        );
+    }
+
+    ///
+    /// Reports the NUMA node of each page of the mapped memory object (header included),
+    /// None for the pages that were not faulted in yet (see [prefault](Self::prefault)).
+    ///
+    pub fn page_nodes(&self) -> Result<Vec<Option<u32>>, Error> {
+        numa::page_nodes(
+            self.base(),
+            self.definition.size.get(),
+            self.definition.page_size(),
+        )
+        .map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Changes the accesses allowed to the given range of the mapped memory object (with
+    /// mprotect), e.g. to make a region read-only once it is fully written. Accessing the range
+    /// against its protection faults (SIGSEGV), through this map only: other mappings of the
+    /// object keep their own protections.
+    ///
+    /// The range is given from [head](Self::head), and must start on a page and end on a page
+    /// or at the end of the object, otherwise a
+    /// [ProtectionMisaligned](ErrorCode::ProtectionMisaligned) error is returned.
+    ///
+    /// # Safety
+    ///
+    /// The range must not be accessed through this map against its new protection until it is
+    /// restored: no reference obtained from it before (e.g. with [view](Self::view) or
+    /// [as_slice](Self::as_slice)) may be read under [Protection::None] or written under
+    /// [Protection::Read], nor one obtained afterwards, and [prefault](Self::prefault) must not
+    /// be called in the meantime.
+    ///
+    /// ```
+    /// use rshm::shm::{Protection, ShmDefinition};
+    ///
+    /// let definition = ShmDefinition::new("example_protect", std::num::NonZero::new(8192).unwrap());
+    /// let mut owned_shm = definition.create().unwrap();
+    /// owned_shm.view_array_mut::<u64>(0, 4).unwrap().copy_from_slice(&[1, 2, 3, 4]);
+    /// // The first page is only read from now on.
+    /// unsafe { owned_shm.protect(0..4096, Protection::Read) }.unwrap();
+    /// assert_eq!(3, owned_shm.view_array::<u64>(0, 4).unwrap()[2]);
+    /// *owned_shm.view_mut::<u64>(4096).unwrap() = 5;
+    /// ```
+    ///
+    pub unsafe fn protect(&self, range: Range<usize>, protection: Protection) -> Result<(), Error> {
+        protection::protect(
+            self.head(),
+            self.len(),
+            range,
+            protection,
+            self.definition.page_size(),
+        )
+        .map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Whether the owner of the shared memory object died without unlinking it. The owner is
+    /// recorded in the header, which is required.
+    ///
+    /// The owner is identified by its process id and start time, so that a process reusing the id
+    /// of a dead owner is not mistaken for it. Objects given up with
+    /// [into_unowned](OwnedShmMap::into_unowned) are not stale.
+    ///
+    pub fn is_stale(&self) -> Result<bool, Error> {
+        if self.definition.options.header {
+            Ok(unsafe { Header::at(self.base()) }.is_stale())
+        } else {
+            Err(self.definition.error(ErrorCode::HeaderMissing))
+        }
+    }
+
+    ///
+    /// Returns the seals of the shared memory object (see [OwnedShmMap::seal]), empty when it
+    /// cannot be sealed.
+    ///
+    pub fn seals(&self) -> Result<SealFlag, Error> {
+        seal::seals(&self.fd).map_err(|error| self.definition.error(error))
+    }
+
+    fn check_header(&self) -> Result<(), Error> {
+        if self.definition.options.header {
+            unsafe { Header::at(self.base()) }
+                .check(self.definition.size, self.definition.options.fingerprint)
+                .map_err(|error| self.definition.error(error))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// returns the mapped memory object as a slice of bytes
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.head(), self.len()) }
+    }
+
+    ///
+    /// Views the value of type T at the given offset from [head](Self::head).
+    ///
+    /// Returns an error when the value does not fit in the mapped memory object
+    /// or is not aligned.
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_view", std::num::NonZero::new(1024).unwrap());
+    /// let shm = definition.create().unwrap();
+    ///
+    /// shm.view::<AtomicU64>(8).unwrap().store(42, Ordering::Release);
+    /// assert_eq!(42, shm.view_array::<u64>(0, 2).unwrap()[1]);
+    /// assert!(shm.view::<u64>(4).is_err());
+    /// assert!(shm.view::<u64>(1024).is_err());
+    /// ```
+    ///
+    pub fn view<T: ShmSafe>(&self, offset: usize) -> Result<&T, Error> {
+        view::checked_array(self.head(), self.len(), offset, 1).map(|ptr| unsafe { &*ptr })
+    }
+
+    ///
+    /// Views the `count` values of type T at the given offset from [head](Self::head).
+    ///
+    pub fn view_array<T: ShmSafe>(&self, offset: usize, count: usize) -> Result<&[T], Error> {
+        view::checked_array(self.head(), self.len(), offset, count)
+            .map(|ptr| unsafe { std::slice::from_raw_parts(ptr, count) })
+    }
+
+    ///
+    /// Views the value of type T registered with the given name in the directory at the start of
+    /// the object (see [OwnedShmMap::register]).
+    ///
+    /// Returns an [ObjectNotFound](ErrorCode::ObjectNotFound) error when no object has the name,
+    /// a [LayoutMismatch](ErrorCode::LayoutMismatch) error when it was registered with
+    /// another layout than that of T, and a [DirectoryMissing](ErrorCode::DirectoryMissing) error
+    /// when the object was not created [with_directory](ShmDefinition::with_directory).
+    ///
+    pub fn lookup<T: ShmSafe>(&self, name: &str) -> Result<&T, Error> {
+        directory::lookup(self.as_slice(), name, header::layout_fingerprint::<T>())
+            .and_then(|(offset, _)| self.view(offset))
+            .map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Views the values of type T registered with the given name in the directory at the start
+    /// of the object (see [lookup](Self::lookup)).
+    ///
+    pub fn lookup_array<T: ShmSafe>(&self, name: &str) -> Result<&[T], Error> {
+        directory::lookup(self.as_slice(), name, header::layout_fingerprint::<T>())
+            .and_then(|(offset, len)| self.view_array(offset, len / size_of::<T>().max(1)))
+            .map_err(|error| self.definition.error(error))
+    }
+
+    // The mutable accessors are only exposed by the maps of objects mapped for writing.
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        debug_assert!(self.writable);
+        unsafe { std::slice::from_raw_parts_mut(self.head() as *mut u8, self.len()) }
+    }
+
+    fn view_mut<T: ShmSafe>(&mut self, offset: usize) -> Result<&mut T, Error> {
+        debug_assert!(self.writable);
+        view::checked_array(self.head(), self.len(), offset, 1)
+            .map(|ptr: *const T| unsafe { &mut *(ptr as *mut T) })
+    }
+
+    fn view_array_mut<T: ShmSafe>(
+        &mut self,
+        offset: usize,
+        count: usize,
+    ) -> Result<&mut [T], Error> {
+        debug_assert!(self.writable);
+        view::checked_array(self.head(), self.len(), offset, count)
+            .map(|ptr: *const T| unsafe { std::slice::from_raw_parts_mut(ptr as *mut T, count) })
+    }
+
+    /// Remaps the shared memory object when its owner grew it (see [ShmMap::refresh]).
+    fn refresh(&mut self) -> Result<bool, Error> {
+        let generation = if self.definition.options.header {
+            let generation = unsafe { Header::at(self.base()) }.generation();
+            if generation == self.generation {
+                return Ok(false);
+            }
+            generation
+        } else {
+            self.generation
+        };
+        let new_size = object_size(&self.fd)
+            .and_then(|size| {
+                NonZero::new(size).ok_or(Error::from(ErrorCode::SizeMismatch {
+                    expected: self.definition.size.get(),
+                    actual: 0,
+                }))
+            })
+            .map_err(|error| self.definition.error(error))?;
+        self.generation = generation;
+        if new_size > self.definition.size {
+            self.remap(new_size)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    ///
+    /// Seals the object while it is not mapped, then maps it again at the same address for
+    /// reading only (see [OwnedShmMap::seal]). The writable mapping is restored when sealing
+    /// fails.
+    ///
+    fn seal(&mut self, seals: SealFlag) -> Result<(), Error> {
+        seal::vacate(self.head, self.definition.size.get())
+            .map_err(|errno| self.definition.error(map_mmap_error(errno)))?;
+        self.writable = false;
+        match seal::add_seals(&self.fd, seals) {
+            Ok(()) => self.map_again(ProtFlags::PROT_READ),
+            Err(error) => {
+                self.map_again(ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)?;
+                Err(self.definition.error(error))
+            }
+        }
+    }
+
+    /// Maps the object again at the address of the mapping, with the given protection.
+    fn map_again(&mut self, flags: ProtFlags) -> Result<(), Error> {
+        self.definition.mmap_at(&self.fd, flags, self.head)?;
+        self.writable = flags.contains(ProtFlags::PROT_WRITE);
+        Ok(())
+    }
+
+    /// Extends the mapping to the new size of the object.
+    fn remap(&mut self, new_size: NonZero<usize>) -> Result<(), Error> {
+        self.head = remap(
+            self.head,
+            self.definition.size,
+            new_size,
+            self.definition.guard_size(),
+        )
+        .map_err(|error| self.definition.error(error))?;
+        self.definition.size = new_size;
+        Ok(())
+    }
+}
+
+impl AsFd for ShmMapping {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+///
+/// A mapped shared memory object that was created by some other process.
+/// It will not be unlinked when dropped.
+///
+#[derive(Debug)]
+pub struct ShmMap {
+    mapping: ShmMapping,
 }
 
 ///
@@ -187,78 +1747,568 @@ pub struct ShmMap {
 ///
 #[derive(Debug)]
 pub struct OwnedShmMap {
-    /// Definition of the shared memory object that is mapped
-    pub definition: ShmDefinition,
-    /// The pointer to the start of the memory mapped object
-    head: NonNull<c_void>,
+    mapping: ShmMapping,
 }
 
 impl Drop for OwnedShmMap {
     fn drop(&mut self) {
-        unsafe { munmap(self.head, self.definition.size.get()) }
-            .map_err(map_munmap_error)
-            .and_then(|_| shm_unlink(self.definition.path.as_str()).map_err(map_unlink_error))
-            .unwrap();
+        // The mapping is unmapped once this returns, when its field is dropped.
+        if let Err(error) = self.definition.unlink() {
+            report_drop_error(&self.definition, &error);
+        }
     }
 }
 
-impl OwnedShmMap {
-    /// returns a pointer to the start of the mapped memory object
-    pub fn head(&self) -> *const u8 {
-        self.head.as_ptr() as *const u8
+impl Deref for OwnedShmMap {
+    type Target = ShmMapping;
+
+    fn deref(&self) -> &ShmMapping {
+        &self.mapping
     }
 }
 
-impl Drop for ShmMap {
-    fn drop(&mut self) {
-        unsafe {
-            munmap(self.head, self.definition.size.get())
-                .map_err(map_munmap_error)
-                .unwrap()
+impl OwnedShmMap {
+    ///
+    /// Unmaps and unlinks the shared memory object, returning the errors that are only
+    /// reported to the [drop error hook](set_drop_error_hook) when the map is dropped.
+    ///
+    /// ```
+    /// use rshm::shm::{ErrorCode, ShmDefinition};
+    ///
+    /// let definition = ShmDefinition::new("example_close", std::num::NonZero::new(1024).unwrap());
+    /// let owned_shm = definition.create().unwrap();
+    /// std::fs::remove_file("/dev/shm/example_close").unwrap();
+    /// assert_eq!(ErrorCode::UnlinkingANonExistentFile, owned_shm.close().unwrap_err());
+    /// ```
+    ///
+    pub fn close(self) -> Result<(), Error> {
+        let mapping = self.into_mapping();
+        let unlinked = mapping.definition.unlink();
+        mapping.close().and(unlinked)
+    }
+
+    ///
+    /// Gives up the ownership of the shared memory object, which will not be unlinked when
+    /// the returned map is dropped: the object outlives this process until another process
+    /// [adopts](ShmMap::adopt) it.
+    ///
+    /// With a [header](ShmDefinition::with_header), the transfer is recorded in it so that a
+    /// single process owns the object at any time.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition = ShmDefinition::new("example_transfer", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header();
+    /// let shm = definition.create().unwrap().into_unowned();
+    /// drop(shm);
+    ///
+    /// let definition = ShmDefinition::new("example_transfer", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header();
+    /// let owned_shm = definition.open().unwrap().adopt().unwrap();
+    /// drop(owned_shm);
+    /// assert!(!std::path::Path::new("/dev/shm/example_transfer").exists());
+    /// ```
+    ///
+    pub fn into_unowned(self) -> ShmMap {
+        let mut mapping = self.into_mapping();
+        if mapping.definition.options.header {
+            let header = unsafe { Header::at(mapping.base()) };
+            header.release_ownership();
+            mapping.generation = header.generation();
         }
+        ShmMap { mapping }
+    }
+
+    /// Takes the mapping out of the map, which is not unlinked.
+    fn into_mapping(self) -> ShmMapping {
+        let map = ManuallyDrop::new(self);
+        unsafe { ptr::read(&map.mapping) }
+    }
+
+    /// returns the mapped memory object as a mutable slice of bytes
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.mapping.as_mut_slice()
+    }
+
+    ///
+    /// Mutably views the value of type T at the given offset from [head](ShmMapping::head).
+    ///
+    pub fn view_mut<T: ShmSafe>(&mut self, offset: usize) -> Result<&mut T, Error> {
+        self.mapping.view_mut(offset)
+    }
+
+    ///
+    /// Mutably views the `count` values of type T at the given offset from
+    /// [head](ShmMapping::head).
+    ///
+    pub fn view_array_mut<T: ShmSafe>(
+        &mut self,
+        offset: usize,
+        count: usize,
+    ) -> Result<&mut [T], Error> {
+        self.mapping.view_array_mut(offset, count)
+    }
+
+    ///
+    /// Registers an object with the given name and layout in the directory at the start of the
+    /// shared memory object, returning its offset from [head](ShmMapping::head).
+    ///
+    /// The directory is formatted by [create](ShmDefinition::create) in the first 4096 bytes
+    /// following [head](ShmMapping::head) when the object is defined
+    /// [with_directory](ShmDefinition::with_directory): registering in another object returns a
+    /// [DirectoryMissing](ErrorCode::DirectoryMissing) error rather than overwriting its memory.
+    /// The directory holds up to 63 objects, with names of up to [MAX_NAME_LEN] bytes, which other
+    /// processes find with [ShmMapping::lookup].
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use rshm::shm::{ShmDefinition, ShmLayout};
+    ///
+    /// let definition = ShmDefinition::new("example_register", std::num::NonZero::new(8192).unwrap())
+    ///     .with_directory();
+    /// let mut shm = definition.create().unwrap();
+    /// shm.register("counter", ShmLayout::of::<AtomicU64>()).unwrap();
+    /// let log = shm.register("log", ShmLayout::array::<u64>(16)).unwrap();
+    /// shm.view_array_mut::<u64>(log, 16).unwrap()[0] = 7;
+    /// shm.lookup::<AtomicU64>("counter").unwrap().store(1, Ordering::Release);
+    ///
+    /// let definition = ShmDefinition::new("example_register", std::num::NonZero::new(8192).unwrap());
+    /// let reader = definition.open().unwrap();
+    /// assert_eq!(1, reader.lookup::<AtomicU64>("counter").unwrap().load(Ordering::Acquire));
+    /// assert_eq!(7, reader.lookup_array::<u64>("log").unwrap()[0]);
+    /// assert!(reader.lookup::<u32>("counter").is_err());
+    /// ```
+    ///
+    pub fn register(&mut self, name: &str, layout: ShmLayout) -> Result<usize, Error> {
+        directory::register(self.mapping.as_mut_slice(), name, &layout)
+            .map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Marks the shared memory object as initialized in its header, releasing the processes
+    /// waiting in [open_wait](ShmDefinition::open_wait).
+    ///
+    pub fn mark_initialized(&self) -> Result<(), Error> {
+        if self.definition.options.header {
+            unsafe { Header::at(self.base()) }.mark_initialized();
+            Ok(())
+        } else {
+            Err(self.definition.error(ErrorCode::HeaderMissing))
+        }
+    }
+
+    ///
+    /// Grows the shared memory object to the given size and remaps it.
+    ///
+    /// The object is extended with ftruncate and the mapping with mremap, which may move it:
+    /// pointers obtained from [head](ShmMapping::head) before growing are invalid afterwards.
+    /// With a [header](ShmDefinition::with_header), its generation is incremented so that other
+    /// processes notice the growth when they [refresh](ShmMap::refresh) their mapping, which
+    /// they must do explicitly: their maps do not grow on their own.
+    ///
+    /// ```
+    /// use rshm::shm::ShmDefinition;
+    ///
+    /// let definition_owned = ShmDefinition::new("example_grow", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header();
+    /// let definition = ShmDefinition::new("example_grow", std::num::NonZero::new(8192).unwrap())
+    ///     .with_header();
+    /// let mut owned_shm = definition_owned.create().unwrap();
+    /// let mut shm = definition.open().unwrap();
+    ///
+    /// owned_shm.grow(std::num::NonZero::new(16384).unwrap()).unwrap();
+    /// assert!(shm.refresh().unwrap());
+    /// assert_eq!(16384 - 4096, shm.len());
+    /// ```
+    ///
+    pub fn grow(&mut self, new_size: NonZero<usize>) -> Result<(), Error> {
+        let new_size = match &self.definition.options.huge_pages {
+            None => new_size,
+            Some(huge_pages) => huge_pages.size.round_up(new_size),
+        };
+        if new_size < self.definition.size {
+            return Err(self.definition.error(ErrorCode::InvalidTruncationSize));
+        }
+        ftruncate(&self.fd, new_size.get() as off_t)
+            .map_err(|errno| self.definition.error(map_truncate_error(errno)))?;
+        self.mapping.remap(new_size)?;
+        if self.definition.options.header {
+            unsafe { Header::at(self.base()) }.publish_size(new_size);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Sends the file descriptor of the shared memory object to the peer of the given
+    /// unix stream, which can map it with [ShmMap::receive].
+    ///
+    pub fn send(&self, stream: &UnixStream) -> Result<(), Error> {
+        send_fd(stream, self.fd.as_fd()).map_err(|error| self.definition.error(error))
+    }
+
+    ///
+    /// Seals the shared memory object, created [with_memfd](ShmDefinition::with_memfd), once it
+    /// is filled: e.g. with F_SEAL_WRITE, F_SEAL_SHRINK and F_SEAL_GROW, its content can never
+    /// change again (see [require_seals](ShmDefinition::require_seals)).
+    ///
+    /// As F_SEAL_WRITE requires the object to have no shared mapping that could be written, its
+    /// mapping is replaced by reserved pages while it is sealed, then it is mapped at the same
+    /// address for reading only. The ownership of the object is given up, as with
+    /// [into_unowned](Self::into_unowned).
+    ///
+    /// When sealing fails (e.g. another process maps the object for writing), the map is
+    /// returned with the error, still mapped for writing and owning the object. Only if the
+    /// object could not be mapped again are the accesses to the returned map faulting.
+    ///
+    // The map is handed back with the error, however large, so that it can still be used.
+    #[allow(clippy::result_large_err)]
+    pub fn seal(mut self, seals: SealFlag) -> Result<ReadOnlyShmMap, (Self, Error)> {
+        let refused = match self.seals() {
+            _ if !self.definition.options.memfd => {
+                Some(self.definition.error(ErrorCode::SealingRefused))
+            }
+            Ok(current) if current.contains(SealFlag::F_SEAL_SEAL) => {
+                Some(self.definition.error(ErrorCode::SealingRefused))
+            }
+            Ok(_) => None,
+            Err(error) => Some(error),
+        };
+        if let Some(error) = refused {
+            return Err((self, error));
+        }
+        // The header cannot be written once the object is sealed: the ownership is given up first.
+        if self.definition.options.header {
+            let header = unsafe { Header::at(self.base()) };
+            header.release_ownership();
+            self.mapping.generation = header.generation();
+        }
+        match self.mapping.seal(seals) {
+            Ok(()) => Ok(ReadOnlyShmMap {
+                mapping: self.into_mapping(),
+            }),
+            Err(error) => {
+                if self.definition.options.header && self.mapping.writable {
+                    let _owned = unsafe { Header::at(self.base()) }.acquire_ownership();
+                }
+                Err((self, error))
+            }
+        }
+    }
+}
+
+impl AsFd for OwnedShmMap {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRef<[u8]> for OwnedShmMap {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsMut<[u8]> for OwnedShmMap {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+///
+/// A shared memory object created by some other process and mapped for reading only.
+/// It will not be unlinked when dropped.
+///
+#[derive(Debug)]
+pub struct ReadOnlyShmMap {
+    mapping: ShmMapping,
+}
+
+impl Deref for ReadOnlyShmMap {
+    type Target = ShmMapping;
+
+    fn deref(&self) -> &ShmMapping {
+        &self.mapping
+    }
+}
+
+impl ReadOnlyShmMap {
+    ///
+    /// Unmaps the shared memory object, returning the errors that are only reported to the
+    /// [drop error hook](set_drop_error_hook) when the map is dropped.
+    ///
+    pub fn close(self) -> Result<(), Error> {
+        self.mapping.close()
+    }
+
+    ///
+    /// Unlinks the shared memory object (although this process did not create it) and unmaps it.
+    /// Processes mapping the object keep their mapping, but it cannot be opened anymore.
+    ///
+    pub fn unlink(self) -> Result<(), Error> {
+        let unlinked = self.definition.unlink();
+        self.close().and(unlinked)
+    }
+
+    ///
+    /// Remaps the shared memory object when its owner grew it (see [OwnedShmMap::grow]).
+    ///
+    /// Returns true when the object was remapped, in which case pointers obtained from
+    /// [head](ShmMapping::head) before refreshing are invalid. With a
+    /// [header](ShmDefinition::with_header), growth is detected from its generation without a
+    /// system call, otherwise the size of the object is read with fstat.
+    ///
+    /// Growth is never picked up implicitly: [head](ShmMapping::head), [len](ShmMapping::len)
+    /// and the views keep covering the previous size until the map is refreshed, so consumers
+    /// call refresh before accessing the part of the object that may have grown.
+    ///
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        self.mapping.refresh()
+    }
+}
+
+impl AsFd for ReadOnlyShmMap {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRef<[u8]> for ReadOnlyShmMap {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl Deref for ShmMap {
+    type Target = ShmMapping;
+
+    fn deref(&self) -> &ShmMapping {
+        &self.mapping
     }
 }
 
 impl ShmMap {
-    /// returns a pointer to the start of the mapped memory object
-    pub fn head(&self) -> *const u8 {
-        self.head.as_ptr() as *const u8
+    ///
+    /// Unmaps the shared memory object, returning the errors that are only reported to the
+    /// [drop error hook](set_drop_error_hook) when the map is dropped.
+    ///
+    pub fn close(self) -> Result<(), Error> {
+        self.mapping.close()
+    }
+
+    ///
+    /// Unlinks the shared memory object (although this process did not create it) and unmaps it.
+    /// Processes mapping the object keep their mapping, but it cannot be opened anymore.
+    ///
+    pub fn unlink(self) -> Result<(), Error> {
+        let unlinked = self.definition.unlink();
+        self.close().and(unlinked)
+    }
+
+    /// returns the mapped memory object as a mutable slice of bytes
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        self.mapping.as_mut_slice()
     }
+
+    ///
+    /// Mutably views the value of type T at the given offset from [head](ShmMapping::head).
+    ///
+    pub fn view_mut<T: ShmSafe>(&mut self, offset: usize) -> Result<&mut T, Error> {
+        self.mapping.view_mut(offset)
+    }
+
+    ///
+    /// Mutably views the `count` values of type T at the given offset from
+    /// [head](ShmMapping::head).
+    ///
+    pub fn view_array_mut<T: ShmSafe>(
+        &mut self,
+        offset: usize,
+        count: usize,
+    ) -> Result<&mut [T], Error> {
+        self.mapping.view_array_mut(offset, count)
+    }
+
+    ///
+    /// Remaps the shared memory object when its owner grew it (see [OwnedShmMap::grow]).
+    ///
+    /// Returns true when the object was remapped, in which case pointers obtained from
+    /// [head](ShmMapping::head) before refreshing are invalid. With a
+    /// [header](ShmDefinition::with_header), growth is detected from its generation without a
+    /// system call, otherwise the size of the object is read with fstat.
+    ///
+    /// Growth is never picked up implicitly: [head](ShmMapping::head), [len](ShmMapping::len)
+    /// and the views keep covering the previous size until the map is refreshed, so consumers
+    /// call refresh before accessing the part of the object that may have grown.
+    ///
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        self.mapping.refresh()
+    }
+
+    ///
+    /// Takes over the ownership of a shared memory object given up with
+    /// [into_unowned](OwnedShmMap::into_unowned): the object will be unlinked when the
+    /// returned map is dropped.
+    ///
+    /// The ownership is recorded in the header of the object, which is required. Adoption fails
+    /// with [AlreadyOwned](ErrorCode::AlreadyOwned) while another process owns the object,
+    /// in which case this map is dropped.
+    ///
+    pub fn adopt(self) -> Result<OwnedShmMap, Error> {
+        if !self.definition.options.header {
+            return Err(self.definition.error(ErrorCode::HeaderMissing));
+        }
+        unsafe { Header::at(self.base()) }
+            .acquire_ownership()
+            .map_err(|error| self.definition.error(error))?;
+        Ok(OwnedShmMap {
+            mapping: self.mapping,
+        })
+    }
+
+    ///
+    /// Receives the file descriptor of a shared memory object sent with [OwnedShmMap::send]
+    /// and maps the whole object.
+    ///
+    pub fn receive(stream: &UnixStream) -> Result<ShmMap, Error> {
+        let fd = recv_fd(stream)?;
+        ShmDefinition::new(fd_label(&fd), NonZero::<usize>::MIN)
+            .with_memfd()
+            .with_discovered_size()
+            .open_fd(fd)
+    }
+}
+
+impl AsFd for ShmMap {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRef<[u8]> for ShmMap {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsMut<[u8]> for ShmMap {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.as_mut_slice()
+    }
+}
+
+///
+/// The label of an anonymous shared memory object, as shown in /proc/self/fd
+/// (e.g. "/memfd:label (deleted)").
+///
+fn fd_label<Fd: AsRawFd>(fd: &Fd) -> String {
+    readlink(format!("/proc/self/fd/{}", fd.as_raw_fd()).as_str())
+        .map(|link| {
+            let link = link.to_string_lossy();
+            link.strip_prefix("/memfd:")
+                .map(|label| label.trim_end_matches(" (deleted)").to_string())
+                .unwrap_or_else(|| link.to_string())
+        })
+        .unwrap_or_default()
+}
+
+///
+/// Extends a mapping to a new size, moving it if needed (with its guard pages, if any).
+///
+fn remap(
+    head: NonNull<c_void>,
+    size: NonZero<usize>,
+    new_size: NonZero<usize>,
+    guard: usize,
+) -> Result<NonNull<c_void>, Error> {
+    if guard > 0 {
+        return guard::remap(head, size.get(), new_size.get(), guard).map_err(map_mremap_error);
+    }
+    unsafe {
+        mremap(
+            head,
+            size.get(),
+            new_size.get(),
+            MRemapFlags::MREMAP_MAYMOVE,
+            None,
+        )
+    }
+    .map_err(map_mremap_error)
 }
 
-fn map_unlink_error(errno: Errno) -> ErrorCode {
-    match errno {
+///
+/// The size of the object referred to by the given file descriptor.
+///
+fn object_size<Fd: AsFd>(fd: &Fd) -> Result<usize, Error> {
+    fstat(fd)
+        .map(|stat| stat.st_size as usize)
+        .map_err(map_fstat_error)
+}
+
+fn map_unlink_error(operation: &'static str, errno: Errno) -> Error {
+    let code = match errno {
         Errno::ENOENT => ErrorCode::UnlinkingANonExistentFile,
         other => ErrorCode::Unknown(other),
-    }
+    };
+    Error::from_errno(code, operation, errno)
+}
+
+fn map_fstat_error(errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EACCES => ErrorCode::ShmPathAccessDenied,
+        Errno::ENOMEM => ErrorCode::OutOfMemory,
+        other => ErrorCode::Unknown(other),
+    };
+    Error::from_errno(code, "fstat", errno)
+}
+
+fn map_munmap_error(errno: Errno) -> Error {
+    Error::from_errno(ErrorCode::Unknown(errno), "munmap", errno)
 }
 
-fn map_munmap_error(errno: Errno) -> ErrorCode {
-    match errno {
+fn map_mmap_error(errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EINVAL => ErrorCode::InvalidMMapArguments,
+        Errno::ENOMEM => ErrorCode::OutOfMemory,
+        Errno::EPERM => ErrorCode::MissingPermission,
         other => ErrorCode::Unknown(other),
-    }
+    };
+    Error::from_errno(code, "mmap", errno)
 }
 
-fn map_mmap_error(errno: Errno) -> ErrorCode {
-    match errno {
+fn map_mremap_error(errno: Errno) -> Error {
+    let code = match errno {
         Errno::EINVAL => ErrorCode::InvalidMMapArguments,
         Errno::ENOMEM => ErrorCode::OutOfMemory,
-        Errno::EPERM => ErrorCode::MissingPermission,
         other => ErrorCode::Unknown(other),
-    }
+    };
+    Error::from_errno(code, "mremap", errno)
 }
 
-fn map_truncate_error(errno: Errno) -> ErrorCode {
-    match errno {
+fn map_truncate_error(errno: Errno) -> Error {
+    let code = match errno {
         Errno::EINTR => ErrorCode::TruncateInterrupted,
         Errno::EINVAL => ErrorCode::InvalidTruncationSize,
         Errno::E2BIG => ErrorCode::InvalidTruncationSize,
         other => ErrorCode::Unknown(other),
-    }
+    };
+    Error::from_errno(code, "ftruncate", errno)
+}
+
+fn map_memfd_error(errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EINVAL => ErrorCode::ShmPathInvalid,
+        Errno::EMFILE => ErrorCode::ProcessTooManyOpenFD,
+        Errno::ENFILE => ErrorCode::SystemTooManyOpenFiles,
+        Errno::ENOMEM => ErrorCode::OutOfMemory,
+        Errno::EPERM => ErrorCode::MissingPermission,
+        other => ErrorCode::Unknown(other),
+    };
+    Error::from_errno(code, "memfd_create", errno)
 }
 
-fn map_open_error(errno: Errno) -> ErrorCode {
-    match errno {
+fn map_open_error(operation: &'static str, errno: Errno) -> Error {
+    let code = match errno {
         Errno::EACCES => ErrorCode::ShmPathAccessDenied,
         Errno::EEXIST => ErrorCode::ShmPathAlreadyExists,
         Errno::EINVAL => ErrorCode::ShmPathInvalid,
@@ -267,22 +2317,29 @@ fn map_open_error(errno: Errno) -> ErrorCode {
         Errno::ENFILE => ErrorCode::SystemTooManyOpenFiles,
         Errno::ENOENT => ErrorCode::ShmPathDoesNotExist,
         other => ErrorCode::Unknown(other),
-    }
+    };
+    Error::from_errno(code, operation, errno)
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::ErrorKind;
+    use std::os::fd::AsFd;
+    use std::ptr;
 
     use crate::shm::ErrorCode;
+    use crate::Error;
 
-    use super::ShmDefinition;
+    use super::{
+        guard, Header, HugePageSize, NumaPolicy, Protection, SealFlag, ShmDefinition, ShmLayout,
+    };
 
     #[test]
     fn create_a_shared_memory_object_with_the_correct_size() {
         let definition = ShmDefinition {
             path: "test1".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let _shm = definition.create().unwrap();
         let metadata = std::fs::metadata("/dev/shm/test1").unwrap();
@@ -296,6 +2353,7 @@ mod tests {
         let definition = ShmDefinition {
             path: "test2".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let shm = definition.create().unwrap();
         drop(shm);
@@ -310,18 +2368,20 @@ mod tests {
         let definition_owned = ShmDefinition {
             path: "test3".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let definition = ShmDefinition {
             path: "test3".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let owned_shm = definition_owned.create().unwrap();
         let shm = definition.open().unwrap();
 
         unsafe { (owned_shm.head() as *mut u8).write(8) };
-        assert_eq!(8, unsafe { (shm.head() as *const u8).read() });
+        assert_eq!(8, unsafe { shm.head().read() });
         unsafe { (owned_shm.head() as *mut u8).write(0) };
-        assert_eq!(0, unsafe { (shm.head() as *const u8).read() });
+        assert_eq!(0, unsafe { shm.head().read() });
     }
 
     #[test]
@@ -329,10 +2389,12 @@ mod tests {
         let definition_owned = ShmDefinition {
             path: "test4".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let definition = ShmDefinition {
             path: "test4".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let _owned_shm = definition_owned.create().unwrap();
         let shm = definition.open().unwrap();
@@ -348,6 +2410,7 @@ mod tests {
         let definition = ShmDefinition {
             path: "/dev/shm/test".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let error = definition.create().unwrap_err();
 
@@ -359,10 +2422,12 @@ mod tests {
         let definition1 = ShmDefinition {
             path: "test6".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let definition2 = ShmDefinition {
             path: "test6".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let _shm = definition1.create().unwrap();
         let error = definition2.create().unwrap_err();
@@ -375,9 +2440,854 @@ mod tests {
         let definition = ShmDefinition {
             path: "test7".to_string(),
             size: std::num::NonZero::new(1024).expect("1024 is not zero"),
+            ..Default::default()
         };
         let error = definition.open().unwrap_err();
 
         assert_eq!(ErrorCode::ShmPathDoesNotExist, error);
     }
+
+    #[test]
+    fn create_reports_an_error_when_no_huge_pages_are_reserved() {
+        let reserved =
+            std::fs::read_to_string("/sys/kernel/mm/hugepages/hugepages-2048kB/nr_hugepages");
+        if reserved.is_ok_and(|reserved| reserved.trim() == "0") {
+            let definition = ShmDefinition::new(
+                "test8",
+                std::num::NonZero::new(1024).expect("1024 is not zero"),
+            )
+            .with_huge_pages(HugePageSize::TwoMiB);
+            let error = definition.create().unwrap_err();
+
+            assert_eq!(ErrorCode::NoHugePagesReserved, error);
+        }
+    }
+
+    #[test]
+    fn open_reports_an_error_when_huge_pages_mount_does_not_exist() {
+        let definition = ShmDefinition::new(
+            "test9",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        )
+        .with_huge_pages_at(HugePageSize::TwoMiB, "/non/existent/hugepages");
+        let error = definition.open().unwrap_err();
+
+        assert_eq!(ErrorCode::ShmPathDoesNotExist, error);
+    }
+
+    #[test]
+    fn huge_pages_definition_rounds_the_size_up_to_the_page_size() {
+        let definition = ShmDefinition::new(
+            "test10",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        )
+        .with_huge_pages(HugePageSize::OneGiB);
+
+        assert_eq!(1024 * 1024 * 1024, definition.size.get());
+    }
+
+    #[test]
+    fn create_memfd_does_not_create_a_named_shared_memory_object() {
+        let definition = ShmDefinition::new(
+            "test11",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        )
+        .with_memfd();
+        let _shm = definition.create().unwrap();
+
+        let metadata_result = std::fs::metadata("/dev/shm/test11");
+        let err = metadata_result.expect_err("memfd created a named object.");
+        assert_eq!(ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn open_reports_an_error_when_definition_is_memfd() {
+        let definition = ShmDefinition::new(
+            "test12",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        )
+        .with_memfd();
+        let error = definition.open().unwrap_err();
+
+        assert_eq!(ErrorCode::AnonymousShmCannotBeOpened, error);
+    }
+
+    #[test]
+    fn receive_maps_a_memfd_sent_by_its_owner() {
+        let definition = ShmDefinition::new(
+            "test13",
+            std::num::NonZero::new(4096).expect("4096 is not zero"),
+        )
+        .with_memfd();
+        let owned_shm = definition.create().unwrap();
+        let (sender, receiver) = std::os::unix::net::UnixStream::pair().unwrap();
+
+        owned_shm.send(&sender).unwrap();
+        let shm = super::ShmMap::receive(&receiver).unwrap();
+
+        assert_eq!("test13", shm.definition.path);
+        assert_eq!(4096, shm.definition.size.get());
+        unsafe { (owned_shm.head() as *mut u8).write(8) };
+        assert_eq!(8, unsafe { shm.head().read() });
+    }
+
+    #[test]
+    fn open_reports_an_error_when_size_does_not_match() {
+        let definition_owned = ShmDefinition::new(
+            "test14",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let definition = ShmDefinition::new(
+            "test14",
+            std::num::NonZero::new(2048).expect("2048 is not zero"),
+        );
+        let _owned_shm = definition_owned.create().unwrap();
+        let error = definition.open().unwrap_err();
+
+        assert_eq!(
+            ErrorCode::SizeMismatch {
+                expected: 2048,
+                actual: 1024
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn open_does_not_resize_nor_remove_the_shared_memory_object() {
+        let definition_owned = ShmDefinition::new(
+            "test15",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let definition = ShmDefinition::new(
+            "test15",
+            std::num::NonZero::new(512).expect("512 is not zero"),
+        );
+        let _owned_shm = definition_owned.create().unwrap();
+        let _error = definition.open().unwrap_err();
+
+        let metadata = std::fs::metadata("/dev/shm/test15").unwrap();
+        assert_eq!(1024, metadata.len());
+    }
+
+    #[test]
+    fn open_with_discovered_size_maps_the_actual_size() {
+        let definition_owned = ShmDefinition::new(
+            "test16",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let definition =
+            ShmDefinition::new("test16", std::num::NonZero::<usize>::MIN).with_discovered_size();
+        let _owned_shm = definition_owned.create().unwrap();
+        let shm = definition.open().unwrap();
+
+        assert_eq!(8192, shm.definition.size.get());
+    }
+
+    #[test]
+    fn open_read_only_maps_an_object_the_process_can_only_read() {
+        let definition_owned = ShmDefinition::new(
+            "test17",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let definition = ShmDefinition::new(
+            "test17",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let owned_shm = definition_owned.create().unwrap();
+        nix::sys::stat::fchmod(&owned_shm, nix::sys::stat::Mode::S_IRUSR).unwrap();
+        let shm = definition.open_read_only().unwrap();
+
+        unsafe { (owned_shm.head() as *mut u8).write(8) };
+        assert_eq!(8, shm.as_slice()[0]);
+        assert_eq!(1024, shm.as_slice().len());
+    }
+
+    #[test]
+    fn open_read_only_reports_an_error_when_path_does_not_exists() {
+        let definition = ShmDefinition::new(
+            "test18",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let error = definition.open_read_only().unwrap_err();
+
+        assert_eq!(ErrorCode::ShmPathDoesNotExist, error);
+    }
+
+    #[test]
+    fn open_wait_returns_once_the_owner_marked_the_object_initialized() {
+        let consumer = std::thread::spawn(|| {
+            let definition = ShmDefinition::new(
+                "test19",
+                std::num::NonZero::new(8192).expect("8192 is not zero"),
+            )
+            .with_header();
+            let shm = definition
+                .open_read_only_wait(std::time::Duration::from_secs(5))
+                .unwrap();
+            (shm.len(), shm.as_slice()[0])
+        });
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let definition_owned = ShmDefinition::new(
+            "test19",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let owned_shm = definition_owned.create().unwrap();
+        unsafe { (owned_shm.head() as *mut u8).write(8) };
+        owned_shm.mark_initialized().unwrap();
+
+        assert_eq!((4096, 8), consumer.join().unwrap());
+    }
+
+    #[test]
+    fn open_wait_reports_an_error_when_the_object_is_not_initialized_in_time() {
+        let definition_owned = ShmDefinition::new(
+            "test20",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let definition = ShmDefinition::new(
+            "test20",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let _owned_shm = definition_owned.create().unwrap();
+        let error = definition
+            .open_wait(std::time::Duration::from_millis(50))
+            .unwrap_err();
+
+        assert_eq!(ErrorCode::WaitTimedOut, error);
+    }
+
+    #[test]
+    fn open_wait_reports_an_error_when_the_object_is_not_created_in_time() {
+        let definition = ShmDefinition::new(
+            "test21",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let error = definition
+            .open_wait(std::time::Duration::from_millis(50))
+            .unwrap_err();
+
+        assert_eq!(ErrorCode::WaitTimedOut, error);
+    }
+
+    #[test]
+    fn open_wait_reports_an_error_when_definition_has_no_header() {
+        let definition = ShmDefinition::new(
+            "test22",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let error = definition
+            .open_wait(std::time::Duration::from_millis(50))
+            .unwrap_err();
+
+        assert_eq!(ErrorCode::HeaderMissing, error);
+    }
+
+    #[test]
+    fn grow_extends_the_shared_memory_object() {
+        let definition = ShmDefinition::new(
+            "test23",
+            std::num::NonZero::new(4096).expect("4096 is not zero"),
+        );
+        let mut shm = definition.create().unwrap();
+        unsafe { (shm.head() as *mut u8).write(8) };
+
+        shm.grow(std::num::NonZero::new(8192).expect("8192 is not zero"))
+            .unwrap();
+
+        let metadata = std::fs::metadata("/dev/shm/test23").unwrap();
+        assert_eq!(8192, metadata.len());
+        assert_eq!(8192, shm.len());
+        assert_eq!(8, unsafe { shm.head().read() });
+        unsafe { (shm.head() as *mut u8).add(8191).write(9) };
+    }
+
+    #[test]
+    fn grow_reports_an_error_when_shrinking() {
+        let definition = ShmDefinition::new(
+            "test24",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let mut shm = definition.create().unwrap();
+
+        let error = shm
+            .grow(std::num::NonZero::new(4096).expect("4096 is not zero"))
+            .unwrap_err();
+
+        assert_eq!(ErrorCode::InvalidTruncationSize, error);
+    }
+
+    #[test]
+    fn refresh_remaps_an_object_grown_by_its_owner() {
+        let definition_owned = ShmDefinition::new(
+            "test25",
+            std::num::NonZero::new(4096).expect("4096 is not zero"),
+        );
+        let definition = ShmDefinition::new(
+            "test25",
+            std::num::NonZero::new(4096).expect("4096 is not zero"),
+        );
+        let mut owned_shm = definition_owned.create().unwrap();
+        let mut shm = definition.open_read_only().unwrap();
+
+        assert!(!shm.refresh().unwrap());
+        owned_shm
+            .grow(std::num::NonZero::new(8192).expect("8192 is not zero"))
+            .unwrap();
+        unsafe { (owned_shm.head() as *mut u8).add(8191).write(9) };
+
+        assert!(shm.refresh().unwrap());
+        assert_eq!(9, shm.as_slice()[8191]);
+    }
+
+    #[test]
+    fn refresh_does_not_remap_when_the_generation_is_unchanged() {
+        let definition_owned = ShmDefinition::new(
+            "test26",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let definition = ShmDefinition::new(
+            "test26",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let mut owned_shm = definition_owned.create().unwrap();
+        let mut shm = definition.open().unwrap();
+
+        assert!(!shm.refresh().unwrap());
+        owned_shm
+            .grow(std::num::NonZero::new(16384).expect("16384 is not zero"))
+            .unwrap();
+        assert!(shm.refresh().unwrap());
+        assert!(!shm.refresh().unwrap());
+        assert_eq!(16384 - 4096, shm.len());
+    }
+
+    #[test]
+    fn create_sets_the_mode_and_group_of_the_shared_memory_object() {
+        use std::os::unix::fs::MetadataExt;
+
+        let gid = nix::unistd::getegid().as_raw();
+        let definition = ShmDefinition::new(
+            "test27",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        )
+        .with_mode(0o660)
+        .with_group(gid);
+        let _shm = definition.create().unwrap();
+
+        let metadata = std::fs::metadata("/dev/shm/test27").unwrap();
+        assert_eq!(0o660, metadata.mode() & 0o777);
+        assert_eq!(gid, metadata.gid());
+    }
+
+    #[test]
+    fn create_maps_with_the_given_flags_and_advice() {
+        let definition = ShmDefinition::new(
+            "test28",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_populate()
+        .with_no_reserve()
+        .with_advice(super::Advice::DontDump)
+        .with_advice(super::Advice::DontFork)
+        .with_advice(super::Advice::WillNeed);
+        let shm = definition.create().unwrap();
+
+        unsafe { (shm.head() as *mut u8).write(8) };
+        assert_eq!(8, unsafe { shm.head().read() });
+    }
+
+    #[test]
+    fn views_of_the_owner_are_read_by_other_processes() {
+        let definition = ShmDefinition::new(
+            "test29",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let mut owned_shm = definition.create().unwrap();
+        owned_shm
+            .view_array_mut::<u32>(8, 2)
+            .unwrap()
+            .copy_from_slice(&[1, 2]);
+        *owned_shm.view_mut::<u64>(16).unwrap() = 3;
+
+        let definition = ShmDefinition::new(
+            "test29",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let shm = definition.open_read_only().unwrap();
+
+        assert_eq!(&[1u32, 2], shm.view_array::<u32>(8, 2).unwrap());
+        assert_eq!(3u64, *shm.view::<u64>(16).unwrap());
+        assert_eq!(3u8, shm.as_slice()[16]);
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: 1032,
+                len: 1024
+            },
+            shm.view::<u64>(1024).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn open_reports_an_error_when_the_object_has_no_header() {
+        let definition = ShmDefinition::new(
+            "test30",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let _owned_shm = definition.create().unwrap();
+
+        let definition = ShmDefinition::new(
+            "test30",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let error = definition.open_read_only().unwrap_err();
+
+        assert_eq!(ErrorCode::InvalidHeader, error);
+    }
+
+    #[test]
+    fn open_checks_the_layout_recorded_in_the_header() {
+        let definition = ShmDefinition::new(
+            "test31",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header()
+        .with_fingerprint(1);
+        let owned_shm = definition.create().unwrap();
+
+        let definition = ShmDefinition::new(
+            "test31",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header()
+        .with_fingerprint(2);
+        let error = definition.open().unwrap_err();
+        let definition = ShmDefinition::new(
+            "test31",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let shm = definition.open_read_only().unwrap();
+
+        assert_eq!(
+            ErrorCode::LayoutMismatch {
+                expected: 2,
+                actual: 1
+            },
+            error
+        );
+        assert_eq!(owned_shm.created(), shm.created());
+        assert!(shm.created().unwrap() <= std::time::SystemTime::now());
+    }
+
+    #[test]
+    fn create_reports_an_error_when_a_layout_is_given_without_header() {
+        let definition = ShmDefinition::new(
+            "test32",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_layout::<u64>();
+
+        assert_eq!(ErrorCode::HeaderMissing, definition.create().unwrap_err());
+    }
+
+    #[test]
+    fn drop_reports_errors_to_the_hook_instead_of_panicking() {
+        static DROP_ERRORS: std::sync::Mutex<Vec<(String, Error)>> =
+            std::sync::Mutex::new(Vec::new());
+        fn record_drop_error(definition: &ShmDefinition, error: &Error) {
+            DROP_ERRORS
+                .lock()
+                .unwrap()
+                .push((definition.path.clone(), error.clone()));
+        }
+        super::set_drop_error_hook(Some(record_drop_error));
+        let definition = ShmDefinition::new(
+            "test33",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let owned_shm = definition.create().unwrap();
+
+        std::fs::remove_file("/dev/shm/test33").unwrap();
+        drop(owned_shm);
+
+        assert!(DROP_ERRORS.lock().unwrap().iter().any(
+            |(path, error)| path == "test33" && *error == ErrorCode::UnlinkingANonExistentFile
+        ));
+    }
+
+    #[test]
+    fn unlink_removes_an_object_created_by_another_process() {
+        let definition = ShmDefinition::new(
+            "test34",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let owned_shm = definition.create().unwrap();
+        let definition = ShmDefinition::new(
+            "test34",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let shm = definition.open().unwrap();
+
+        shm.unlink().unwrap();
+
+        assert!(!std::path::Path::new("/dev/shm/test34").exists());
+        let error = owned_shm.close().unwrap_err();
+        assert_eq!(ErrorCode::UnlinkingANonExistentFile, error);
+        assert_eq!(Some("shm_unlink"), error.operation());
+        assert_eq!(Some("test34"), error.path());
+    }
+
+    #[test]
+    fn adopt_reports_an_error_while_the_object_is_owned() {
+        let definition = ShmDefinition::new(
+            "test35",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let owned_shm = definition.create().unwrap();
+        let open = || {
+            ShmDefinition::new(
+                "test35",
+                std::num::NonZero::new(8192).expect("8192 is not zero"),
+            )
+            .with_header()
+            .open()
+            .unwrap()
+        };
+
+        assert_eq!(
+            ErrorCode::AlreadyOwned {
+                pid: std::process::id() as i32
+            },
+            open().adopt().unwrap_err()
+        );
+        let shm = owned_shm.into_unowned();
+        let adopted_shm = open().adopt().unwrap();
+        assert_eq!(
+            ErrorCode::AlreadyOwned {
+                pid: std::process::id() as i32
+            },
+            open().adopt().unwrap_err()
+        );
+
+        drop(shm);
+        assert!(std::path::Path::new("/dev/shm/test35").exists());
+        drop(adopted_shm);
+        assert!(!std::path::Path::new("/dev/shm/test35").exists());
+    }
+
+    #[test]
+    fn create_or_reclaim_replaces_an_object_left_by_a_dead_owner() {
+        let definition = ShmDefinition::new(
+            "test36",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        )
+        .with_header();
+        let owned_shm = definition.clone().create().unwrap();
+        let shm = definition.clone().open().unwrap();
+        assert!(!shm.is_stale().unwrap());
+        assert_eq!(
+            ErrorCode::AlreadyOwned {
+                pid: std::process::id() as i32
+            },
+            definition.clone().create_or_reclaim().unwrap_err()
+        );
+
+        // Simulates the crash of the owner, which leaves its object behind.
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        child.wait().unwrap();
+        unsafe { Header::at(owned_shm.base()) }.set_owner(child.id() as i32);
+        std::mem::forget(owned_shm);
+        assert!(shm.is_stale().unwrap());
+
+        let owned_shm = definition.create_or_reclaim().unwrap();
+        assert!(!owned_shm.as_slice().is_empty());
+        drop(owned_shm);
+        assert!(!std::path::Path::new("/dev/shm/test36").exists());
+    }
+
+    #[test]
+    fn prefault_makes_locked_pages_resident_without_modifying_them() {
+        let definition = ShmDefinition::new(
+            "test37",
+            std::num::NonZero::new(65536).expect("65536 is not zero"),
+        );
+        let mut owned_shm = definition.create().unwrap();
+        owned_shm.as_mut_slice()[4096] = 8;
+        let definition = ShmDefinition::new(
+            "test37",
+            std::num::NonZero::new(65536).expect("65536 is not zero"),
+        );
+        let shm = definition.open_read_only().unwrap();
+
+        shm.lock().unwrap();
+        shm.prefault();
+        owned_shm.lock().unwrap();
+        owned_shm.prefault();
+
+        let mut residency = [0u8; 16];
+        assert_eq!(0, unsafe {
+            libc::mincore(
+                shm.head() as *mut libc::c_void,
+                65536,
+                residency.as_mut_ptr(),
+            )
+        });
+        assert!(residency.iter().all(|page| page & 1 == 1));
+        assert_eq!(8, owned_shm.as_slice()[4096]);
+        assert_eq!(8, shm.as_slice()[4096]);
+    }
+
+    #[test]
+    fn numa_policy_is_applied_before_populating_the_pages() {
+        let definition = ShmDefinition::new(
+            "test38",
+            std::num::NonZero::new(65536).expect("65536 is not zero"),
+        )
+        .with_numa_policy(NumaPolicy::Interleave(vec![0]))
+        .with_populate();
+        let owned_shm = definition.create().unwrap();
+
+        let nodes = owned_shm.page_nodes().unwrap();
+
+        assert_eq!(16, nodes.len());
+        assert!(nodes.iter().all(|node| *node == Some(0)));
+    }
+
+    #[test]
+    fn sealed_objects_are_only_mapped_for_reading() {
+        let seals = SealFlag::F_SEAL_WRITE | SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_GROW;
+        let definition = || {
+            ShmDefinition::new(
+                "test39",
+                std::num::NonZero::new(8192).expect("8192 is not zero"),
+            )
+            .with_memfd()
+            .with_header()
+        };
+        let owned_shm = definition().create().unwrap();
+        owned_shm.mark_initialized().unwrap();
+        let unsealed_fd = owned_shm.as_fd().try_clone_to_owned().unwrap();
+        let error = definition()
+            .require_seals(seals)
+            .open_fd_read_only(unsealed_fd)
+            .unwrap_err();
+        assert!(matches!(error.code(), Some(ErrorCode::SealsMissing { .. })));
+
+        let sealed_shm = owned_shm.seal(seals).unwrap();
+
+        assert_eq!(seals, sealed_shm.seals().unwrap());
+        let fd = || sealed_shm.as_fd().try_clone_to_owned().unwrap();
+        assert_eq!(
+            ErrorCode::MissingPermission,
+            definition().open_fd(fd()).unwrap_err()
+        );
+        let shm = definition()
+            .require_seals(seals)
+            .open_fd_read_only(fd())
+            .unwrap();
+        assert_eq!(sealed_shm.created(), shm.created());
+    }
+
+    #[test]
+    fn seal_reports_an_error_for_named_objects() {
+        let definition = ShmDefinition::new(
+            "test40",
+            std::num::NonZero::new(1024).expect("1024 is not zero"),
+        );
+        let owned_shm = definition.create().unwrap();
+
+        let (mut owned_shm, error) = owned_shm.seal(SealFlag::F_SEAL_GROW).unwrap_err();
+
+        assert_eq!(ErrorCode::SealingRefused, error);
+        assert!(std::path::Path::new("/dev/shm/test40").exists());
+        owned_shm.as_mut_slice()[0] = 1;
+    }
+
+    #[test]
+    fn seal_returns_the_writable_map_when_sealing_fails() {
+        let seals = SealFlag::F_SEAL_WRITE | SealFlag::F_SEAL_GROW;
+        let definition = || {
+            ShmDefinition::new(
+                "test45",
+                std::num::NonZero::new(8192).expect("8192 is not zero"),
+            )
+            .with_memfd()
+            .with_header()
+        };
+        let owned_shm = definition().create().unwrap();
+        let shm = definition()
+            .open_fd(owned_shm.as_fd().try_clone_to_owned().unwrap())
+            .unwrap();
+
+        let (mut owned_shm, error) = owned_shm.seal(seals).unwrap_err();
+
+        assert_eq!(ErrorCode::SealingRefused, error);
+        assert_eq!(SealFlag::empty(), owned_shm.seals().unwrap());
+        owned_shm.as_mut_slice()[0] = 1;
+        assert_eq!(1, shm.as_slice()[0]);
+        let owner = unsafe { Header::at(owned_shm.base()) }.owner().0;
+        assert_eq!(std::process::id() as i32, owner);
+
+        drop(shm);
+        let sealed_shm = owned_shm.seal(seals).unwrap();
+        assert_eq!(seals, sealed_shm.seals().unwrap());
+        assert_eq!(1, sealed_shm.as_slice()[0]);
+    }
+
+    #[test]
+    fn lookup_finds_the_objects_registered_with_a_header() {
+        let definition = || {
+            ShmDefinition::new(
+                "test41",
+                std::num::NonZero::new(16384).expect("16384 is not zero"),
+            )
+            .with_header()
+            .with_directory()
+        };
+        let mut owned_shm = definition().create().unwrap();
+        let offset = owned_shm
+            .register("config", ShmLayout::of::<[u32; 4]>())
+            .unwrap();
+        owned_shm.view_mut::<[u32; 4]>(offset).unwrap()[3] = 9;
+
+        let shm = definition().open_read_only().unwrap();
+        assert_eq!(&[0, 0, 0, 9], shm.lookup::<[u32; 4]>("config").unwrap());
+        let error = shm.lookup::<u64>("log").unwrap_err();
+        assert_eq!(ErrorCode::ObjectNotFound("log".to_string()), error);
+        assert_eq!(Some("test41"), error.path());
+    }
+
+    #[test]
+    fn register_refuses_objects_created_without_a_directory() {
+        let definition = ShmDefinition::new(
+            "test46",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let mut owned_shm = definition.clone().create().unwrap();
+        owned_shm.as_mut_slice()[0] = 7;
+
+        let error = owned_shm
+            .register("config", ShmLayout::of::<u64>())
+            .unwrap_err();
+        assert_eq!(ErrorCode::DirectoryMissing, error);
+        assert_eq!(7, owned_shm.as_slice()[0]);
+        drop(owned_shm);
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: 4096,
+                len: 4000
+            },
+            ShmDefinition::new(
+                "test46",
+                std::num::NonZero::new(4000).expect("4000 is not zero")
+            )
+            .with_directory()
+            .create()
+            .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn guard_pages_surround_the_mappings() {
+        let definition = || {
+            ShmDefinition::new(
+                "test42",
+                std::num::NonZero::new(8192).expect("8192 is not zero"),
+            )
+            .with_header()
+            .with_guard_pages()
+        };
+        let mut owned_shm = definition().create().unwrap();
+        let shm = definition().open().unwrap();
+
+        for (base, end) in [
+            (
+                owned_shm.base() as usize,
+                owned_shm.head() as usize + owned_shm.len(),
+            ),
+            (shm.base() as usize, shm.head() as usize + shm.len()),
+        ] {
+            assert_eq!(Some("---p".to_string()), guard::permissions(base - 1));
+            assert_eq!(Some("rw-s".to_string()), guard::permissions(base));
+            assert_eq!(Some("---p".to_string()), guard::permissions(end));
+        }
+        owned_shm
+            .grow(std::num::NonZero::new(16384).expect("16384 is not zero"))
+            .unwrap();
+        let end = owned_shm.head() as usize + owned_shm.len();
+        assert_eq!(Some("rw-s".to_string()), guard::permissions(end - 1));
+        assert_eq!(Some("---p".to_string()), guard::permissions(end));
+        assert_eq!(
+            Some("---p".to_string()),
+            guard::permissions(owned_shm.base() as usize - 1)
+        );
+    }
+
+    #[test]
+    fn protect_changes_the_protection_of_a_range() {
+        let definition = || {
+            ShmDefinition::new(
+                "test43",
+                std::num::NonZero::new(12288).expect("12288 is not zero"),
+            )
+        };
+        let owned_shm = definition().create().unwrap();
+        let shm = definition().open().unwrap();
+        let read_only_shm = definition().open_read_only().unwrap();
+
+        unsafe { owned_shm.protect(4096..12288, Protection::Read) }.unwrap();
+        unsafe { shm.protect(0..4096, Protection::None) }.unwrap();
+
+        let owned_head = owned_shm.head() as usize;
+        assert_eq!(Some("rw-s".to_string()), guard::permissions(owned_head));
+        assert_eq!(
+            Some("r--s".to_string()),
+            guard::permissions(owned_head + 4096)
+        );
+        assert_eq!(
+            Some("---s".to_string()),
+            guard::permissions(shm.head() as usize)
+        );
+        assert_eq!(
+            ErrorCode::ProtectionRefused(Protection::ReadWrite),
+            unsafe { read_only_shm.protect(0..4096, Protection::ReadWrite) }.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn writing_to_a_read_only_range_faults() {
+        let definition = ShmDefinition::new(
+            "test44",
+            std::num::NonZero::new(8192).expect("8192 is not zero"),
+        );
+        let owned_shm = definition.create().unwrap();
+        unsafe { owned_shm.protect(4096..8192, Protection::Read) }.unwrap();
+        let head = owned_shm.head() as *mut u8;
+
+        let child = unsafe { libc::fork() };
+        if child == 0 {
+            // The child only writes to the mapping: the first page is writable, not the second.
+            unsafe {
+                ptr::write_volatile(head, 1);
+                ptr::write_volatile(head.add(4096), 1);
+                libc::_exit(0);
+            }
+        }
+        let mut status = 0;
+        assert_eq!(child, unsafe { libc::waitpid(child, &mut status, 0) });
+
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::SIGSEGV, libc::WTERMSIG(status));
+        assert_eq!(1, owned_shm.as_slice()[0]);
+        assert_eq!(0, owned_shm.as_slice()[4096]);
+    }
 }
@@ -0,0 +1,29 @@
+use nix::sys::mman::MmapAdvise;
+
+///
+/// Hints given to the kernel (with madvise) about the use of a mapped shared memory object.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// Back the mapping with transparent huge pages when possible (MADV_HUGEPAGE).
+    /// Shared memory objects need /sys/kernel/mm/transparent_hugepage/shmem_enabled to
+    /// allow it.
+    HugePage,
+    /// Exclude the mapping from core dumps (MADV_DONTDUMP).
+    DontDump,
+    /// Do not make the mapping available to child processes after a fork (MADV_DONTFORK).
+    DontFork,
+    /// Read the pages of the mapping ahead of their first access (MADV_WILLNEED).
+    WillNeed,
+}
+
+impl Advice {
+    pub(super) fn to_mmap_advise(self) -> MmapAdvise {
+        match self {
+            Advice::HugePage => MmapAdvise::MADV_HUGEPAGE,
+            Advice::DontDump => MmapAdvise::MADV_DONTDUMP,
+            Advice::DontFork => MmapAdvise::MADV_DONTFORK,
+            Advice::WillNeed => MmapAdvise::MADV_WILLNEED,
+        }
+    }
+}
@@ -0,0 +1,1053 @@
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::{Deref, Index};
+use std::ptr;
+use std::slice::SliceIndex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::view::{self, ShmSafe};
+use super::{ErrorCode, ShmHeap, ShmPtr};
+use crate::Error;
+
+/// The smallest number of values allocated for a vector.
+const MIN_CAPACITY: usize = 4;
+/// The smallest number of buckets allocated for a hash map.
+const MIN_BUCKETS: usize = 8;
+
+const EMPTY: u64 = 0;
+const FULL: u64 = 1;
+const DELETED: u64 = 2;
+
+///
+/// A vector of values of type T whose values are allocated in a [ShmHeap].
+///
+/// The vector itself is stored in the shared memory object (e.g. allocated with
+/// [ShmHeap::alloc], or nested in another vector), and refers to its values by offset: processes
+/// read it by resolving it against their own map as a [ShmVecRef], without copying it. The process
+/// holding the heap modifies it with [ShmHeap::push], [ShmHeap::pop] or [ShmHeap::slice_mut].
+///
+/// A zeroed vector is empty, so that the vectors of a new object are.
+///
+/// ```
+/// use rshm::shm::{ShmDefinition, ShmHeap, ShmVec};
+///
+/// let definition = ShmDefinition::new("example_vec", std::num::NonZero::new(8192).unwrap());
+/// let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+/// let values = heap.alloc(ShmVec::new()).unwrap();
+/// heap.push(values, 1u64).unwrap();
+/// heap.push(values, 2u64).unwrap();
+///
+/// let definition = ShmDefinition::new("example_vec", std::num::NonZero::new(8192).unwrap());
+/// let shm = definition.open_read_only().unwrap();
+/// let values = values.get(&shm).unwrap().unwrap().resolve(&shm).unwrap();
+/// assert_eq!(2, values[1]);
+/// assert_eq!(vec![1, 2], values.iter().copied().collect::<Vec<_>>());
+/// assert_eq!("[1, 2]", format!("{values:?}"));
+/// ```
+///
+#[repr(C)]
+pub struct ShmVec<T> {
+    /// The bits of the pointer to the values, null until values are allocated.
+    data: AtomicU64,
+    len: AtomicU64,
+    capacity: AtomicU64,
+    value: PhantomData<fn() -> T>,
+}
+
+// Any content is valid: the values are checked against the memory of the object when resolved.
+unsafe impl<T> ShmSafe for ShmVec<T> {}
+
+impl<T> Default for ShmVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> std::fmt::Debug for ShmVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The values are only reachable through the memory of the object, see ShmVecRef.
+        f.debug_struct("ShmVec")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> ShmVec<T> {
+    /// returns an empty vector, allocating nothing
+    pub const fn new() -> Self {
+        ShmVec {
+            data: AtomicU64::new(0),
+            len: AtomicU64::new(0),
+            capacity: AtomicU64::new(0),
+            value: PhantomData,
+        }
+    }
+
+    /// returns the number of values in the vector
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire) as usize
+    }
+
+    /// returns true when the vector holds no value
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// returns the number of values the vector can hold before its values are moved
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Acquire) as usize
+    }
+
+    fn data(&self) -> ShmPtr<T> {
+        ShmPtr::from_bits(self.data.load(Ordering::Acquire))
+    }
+}
+
+impl<T: ShmSafe> ShmVec<T> {
+    ///
+    /// Resolves the values of the vector against the given memory, returning an error when they
+    /// do not fit in it.
+    ///
+    /// The values are those pushed before the call: values pushed later are not part of them.
+    /// When the vector grows, their block is [retired](ShmHeap::retire), so that they stay
+    /// readable until the owner reclaims it.
+    ///
+    pub fn resolve<'a, M: AsRef<[u8]> + ?Sized>(
+        &'a self,
+        memory: &'a M,
+    ) -> Result<ShmVecRef<'a, T>, Error> {
+        // The length is read first: the values it covers were written before it was stored.
+        let len = self.len();
+        if len == 0 {
+            return Ok(ShmVecRef { values: &[] });
+        }
+        let offset = self
+            .data()
+            .offset()
+            .ok_or(ErrorCode::InvalidAllocation { offset: 0 })?;
+        let memory = memory.as_ref();
+        view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, len).map(|ptr| ShmVecRef {
+            values: unsafe { std::slice::from_raw_parts(ptr, len) },
+        })
+    }
+}
+
+///
+/// The values of a [ShmVec] resolved against the memory of a mapped object.
+///
+pub struct ShmVecRef<'a, T> {
+    values: &'a [T],
+}
+
+impl<'a, T> ShmVecRef<'a, T> {
+    /// returns the values of the vector as a slice of the memory
+    pub fn as_slice(&self) -> &'a [T] {
+        self.values
+    }
+}
+
+impl<T> Deref for ShmVecRef<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.values
+    }
+}
+
+impl<T, I: SliceIndex<[T]>> Index<I> for ShmVecRef<'_, T> {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &I::Output {
+        &self.values[index]
+    }
+}
+
+impl<'a, T> IntoIterator for ShmVecRef<'a, T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &ShmVecRef<'a, T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.iter()
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for ShmVecRef<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.values).finish()
+    }
+}
+
+///
+/// A UTF-8 string whose bytes are allocated in a [ShmHeap].
+///
+/// As a [ShmVec], it is stored in the shared memory object and resolved by processes against
+/// their own map, as a `&str`. The process holding the heap appends to it with
+/// [ShmHeap::push_str]. A zeroed string is empty.
+///
+/// ```
+/// use rshm::shm::{ShmDefinition, ShmHeap, ShmVec};
+///
+/// let definition = ShmDefinition::new("example_string", std::num::NonZero::new(8192).unwrap());
+/// let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+/// let names = heap.alloc(ShmVec::new()).unwrap();
+/// let name = heap.alloc_string("first").unwrap();
+/// heap.push(names, name).unwrap();
+///
+/// let names = names.get(&heap).unwrap().unwrap().resolve(&heap).unwrap();
+/// assert_eq!("first", names[0].resolve(&heap).unwrap());
+/// ```
+///
+#[derive(Default)]
+#[repr(C)]
+pub struct ShmString {
+    bytes: ShmVec<u8>,
+}
+
+unsafe impl ShmSafe for ShmString {}
+
+impl std::fmt::Debug for ShmString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShmString")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl ShmString {
+    /// returns an empty string, allocating nothing
+    pub const fn new() -> Self {
+        ShmString {
+            bytes: ShmVec::new(),
+        }
+    }
+
+    /// returns the length of the string in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// returns true when the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    ///
+    /// Resolves the string against the given memory, returning an error when its bytes do not fit
+    /// in it or are not UTF-8.
+    ///
+    pub fn resolve<'a, M: AsRef<[u8]> + ?Sized>(&'a self, memory: &'a M) -> Result<&'a str, Error> {
+        std::str::from_utf8(self.bytes.resolve(memory)?.as_slice()).map_err(|error| {
+            ErrorCode::InvalidUtf8 {
+                valid_up_to: error.valid_up_to(),
+            }
+            .into()
+        })
+    }
+}
+
+///
+/// A hash map whose entries are allocated in a [ShmHeap], with keys of type K and values of
+/// type V.
+///
+/// As a [ShmVec], it is stored in the shared memory object and resolved by processes against
+/// their own map, as a [ShmHashMapRef]. The process holding the heap modifies it with
+/// [ShmHeap::insert] and [ShmHeap::remove]. A zeroed hash map is empty.
+///
+/// Keys are hashed from their value, the same way in every process: they should not hold
+/// pointers into the object (e.g. a [ShmString]), whose value does not identify their content.
+///
+/// ```
+/// use rshm::shm::{ShmDefinition, ShmHashMap, ShmHeap};
+///
+/// let definition = ShmDefinition::new("example_hash_map", std::num::NonZero::new(8192).unwrap());
+/// let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+/// let counters = heap.alloc(ShmHashMap::new()).unwrap();
+/// heap.insert(counters, 7u32, 1u64).unwrap();
+///
+/// let definition = ShmDefinition::new("example_hash_map", std::num::NonZero::new(8192).unwrap());
+/// let shm = definition.open_read_only().unwrap();
+/// let counters = counters.get(&shm).unwrap().unwrap().resolve(&shm).unwrap();
+/// assert_eq!(1, counters[&7]);
+/// assert_eq!(None, counters.get(&8));
+/// ```
+///
+#[repr(C)]
+pub struct ShmHashMap<K, V> {
+    /// The bits of the pointer to the buckets, null until entries are inserted.
+    table: AtomicU64,
+    len: AtomicU64,
+    /// The number of buckets that are not empty, including the removed entries.
+    used: AtomicU64,
+    entry: PhantomData<fn() -> (K, V)>,
+}
+
+unsafe impl<K, V> ShmSafe for ShmHashMap<K, V> {}
+
+impl<K, V> Default for ShmHashMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> std::fmt::Debug for ShmHashMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShmHashMap")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+///
+/// An entry of a hash map. The first bucket of a table holds its number of buckets in its state.
+///
+#[repr(C)]
+struct Bucket<K, V> {
+    state: AtomicU64,
+    key: K,
+    /// The bits of the pointer to the value, allocated on its own so that it is replaced at once.
+    value: AtomicU64,
+    target: PhantomData<fn() -> V>,
+}
+
+unsafe impl<K: ShmSafe, V> ShmSafe for Bucket<K, V> {}
+
+impl<K, V: ShmSafe> Bucket<K, V> {
+    fn value(&self) -> ShmPtr<V> {
+        ShmPtr::from_bits(self.value.load(Ordering::Acquire))
+    }
+
+    /// returns the value of the entry, None when it does not fit in the given memory
+    fn resolve<'a>(&self, memory: &'a [u8]) -> Option<&'a V> {
+        self.value().get(memory).ok().flatten()
+    }
+}
+
+impl<K, V> ShmHashMap<K, V> {
+    /// returns an empty hash map, allocating nothing
+    pub const fn new() -> Self {
+        ShmHashMap {
+            table: AtomicU64::new(0),
+            len: AtomicU64::new(0),
+            used: AtomicU64::new(0),
+            entry: PhantomData,
+        }
+    }
+
+    /// returns the number of entries in the hash map
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire) as usize
+    }
+
+    /// returns true when the hash map holds no entry
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn table(&self) -> ShmPtr<Bucket<K, V>> {
+        ShmPtr::from_bits(self.table.load(Ordering::Acquire))
+    }
+}
+
+impl<K: ShmSafe + Hash + Eq, V: ShmSafe> ShmHashMap<K, V> {
+    ///
+    /// Resolves the entries of the hash map against the given memory, returning an error when
+    /// they do not fit in it.
+    ///
+    pub fn resolve<'a, M: AsRef<[u8]> + ?Sized>(
+        &'a self,
+        memory: &'a M,
+    ) -> Result<ShmHashMapRef<'a, K, V>, Error> {
+        let memory = memory.as_ref();
+        Ok(ShmHashMapRef {
+            map: self,
+            buckets: buckets(memory, self.table())?,
+            memory,
+        })
+    }
+}
+
+///
+/// The entries of a [ShmHashMap] resolved against the memory of a mapped object.
+///
+pub struct ShmHashMapRef<'a, K, V> {
+    map: &'a ShmHashMap<K, V>,
+    buckets: &'a [Bucket<K, V>],
+    memory: &'a [u8],
+}
+
+impl<'a, K: ShmSafe + Hash + Eq, V: ShmSafe> ShmHashMapRef<'a, K, V> {
+    /// returns the number of entries in the hash map
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// returns true when the hash map holds no entry
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// returns the value of the given key, None when the key is not in the hash map (or its value
+    /// does not fit in the memory)
+    pub fn get(&self, key: &K) -> Option<&'a V> {
+        find(self.buckets, key).and_then(|index| self.buckets[index].resolve(self.memory))
+    }
+
+    /// returns true when the given key is in the hash map
+    pub fn contains_key(&self, key: &K) -> bool {
+        find(self.buckets, key).is_some()
+    }
+
+    /// returns an iterator over the entries of the hash map, in no particular order
+    pub fn iter(&self) -> ShmHashMapIter<'a, K, V> {
+        ShmHashMapIter {
+            buckets: self.buckets.iter(),
+            memory: self.memory,
+        }
+    }
+}
+
+impl<K: ShmSafe + Hash + Eq, V: ShmSafe> Index<&K> for ShmHashMapRef<'_, K, V> {
+    type Output = V;
+
+    fn index(&self, key: &K) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<'a, K: ShmSafe + Hash + Eq, V: ShmSafe> IntoIterator for ShmHashMapRef<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = ShmHashMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: ShmSafe + Hash + Eq, V: ShmSafe> IntoIterator for &ShmHashMapRef<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = ShmHashMapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V> std::fmt::Debug for ShmHashMapRef<'_, K, V>
+where
+    K: ShmSafe + Hash + Eq + std::fmt::Debug,
+    V: ShmSafe + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+///
+/// An iterator over the entries of a [ShmHashMapRef].
+///
+pub struct ShmHashMapIter<'a, K, V> {
+    buckets: std::slice::Iter<'a, Bucket<K, V>>,
+    memory: &'a [u8],
+}
+
+impl<'a, K, V: ShmSafe> Iterator for ShmHashMapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let memory = self.memory;
+        self.buckets
+            .by_ref()
+            .filter(|bucket| bucket.state.load(Ordering::Acquire) == FULL)
+            .find_map(|bucket| Some((&bucket.key, bucket.resolve(memory)?)))
+    }
+}
+
+impl<M: AsRef<[u8]> + AsMut<[u8]>> ShmHeap<M> {
+    ///
+    /// Allocates a vector holding a copy of the given values, to be stored in the heap (e.g.
+    /// pushed in another vector).
+    ///
+    pub fn alloc_vec<T: ShmSafe + Copy>(&mut self, values: &[T]) -> Result<ShmVec<T>, Error> {
+        let vec = ShmVec::new();
+        if values.is_empty() {
+            return Ok(vec);
+        }
+        let data = self.alloc_array::<T>(values.len())?;
+        self.array_mut(data, values.len())?.copy_from_slice(values);
+        vec.data.store(data.to_bits(), Ordering::Relaxed);
+        vec.len.store(values.len() as u64, Ordering::Relaxed);
+        vec.capacity.store(values.len() as u64, Ordering::Relaxed);
+        Ok(vec)
+    }
+
+    ///
+    /// Allocates a string holding a copy of the given one, to be stored in the heap (e.g. pushed
+    /// in a vector).
+    ///
+    pub fn alloc_string(&mut self, value: &str) -> Result<ShmString, Error> {
+        self.alloc_vec(value.as_bytes())
+            .map(|bytes| ShmString { bytes })
+    }
+
+    ///
+    /// Makes room in the given vector for at least `additional` more values.
+    ///
+    /// When the vector is full, its values are copied to a block at least twice as large, and the
+    /// previous block is [retired](Self::retire): readers may still be reading it.
+    ///
+    pub fn reserve<T: ShmSafe>(
+        &mut self,
+        vec: ShmPtr<ShmVec<T>>,
+        additional: usize,
+    ) -> Result<(), Error> {
+        let header = self.collection(vec)?;
+        let (data, len, capacity) = (header.data(), header.len(), header.capacity());
+        let required = len
+            .checked_add(additional)
+            .ok_or(ErrorCode::HeapExhausted { size: usize::MAX })?;
+        if required <= capacity {
+            return Ok(());
+        }
+        let capacity = required.max(capacity * 2).max(MIN_CAPACITY);
+        let new_data = self.alloc_array::<T>(capacity)?;
+        if let (Some(from), Some(to)) = (data.offset(), new_data.offset()) {
+            let memory = self.as_mut();
+            view::checked_array::<T>(memory.as_ptr(), memory.len(), from, len)?;
+            memory.copy_within(from..from + len * size_of::<T>(), to);
+        }
+        let header = self.collection(vec)?;
+        header.data.store(new_data.to_bits(), Ordering::Release);
+        header.capacity.store(capacity as u64, Ordering::Release);
+        if !data.is_null() {
+            self.retire(data)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Appends the given value to the vector, moving its values to a larger block when it is
+    /// full (see [reserve](Self::reserve)).
+    ///
+    pub fn push<T: ShmSafe>(&mut self, vec: ShmPtr<ShmVec<T>>, value: T) -> Result<(), Error> {
+        self.reserve(vec, 1)?;
+        let header = self.collection(vec)?;
+        let (data, len) = (header.data(), header.len());
+        unsafe { ptr::write(&mut self.array_mut(data, len + 1)?[len], value) };
+        // Publishes the value to the readers, after it was written.
+        self.collection(vec)?
+            .len
+            .store(len as u64 + 1, Ordering::Release);
+        Ok(())
+    }
+
+    ///
+    /// Appends a copy of the given values to the vector (see [push](Self::push)).
+    ///
+    pub fn extend_from_slice<T: ShmSafe + Copy>(
+        &mut self,
+        vec: ShmPtr<ShmVec<T>>,
+        values: &[T],
+    ) -> Result<(), Error> {
+        self.reserve(vec, values.len())?;
+        let header = self.collection(vec)?;
+        let (data, len) = (header.data(), header.len());
+        if values.is_empty() {
+            return Ok(());
+        }
+        self.array_mut(data, len + values.len())?[len..].copy_from_slice(values);
+        self.collection(vec)?
+            .len
+            .store((len + values.len()) as u64, Ordering::Release);
+        Ok(())
+    }
+
+    ///
+    /// Removes the last value of the vector and returns it, None when the vector is empty.
+    ///
+    /// Its place is reused by the next value pushed: readers should not hold on to it.
+    ///
+    pub fn pop<T: ShmSafe>(&mut self, vec: ShmPtr<ShmVec<T>>) -> Result<Option<T>, Error> {
+        let header = self.collection(vec)?;
+        let (data, len) = (header.data(), header.len());
+        if len == 0 {
+            return Ok(None);
+        }
+        self.collection(vec)?
+            .len
+            .store(len as u64 - 1, Ordering::Release);
+        Ok(Some(unsafe {
+            ptr::read(&self.array_mut(data, len)?[len - 1])
+        }))
+    }
+
+    ///
+    /// Mutably resolves the values of the given vector, e.g. to update them in place.
+    ///
+    pub fn slice_mut<T: ShmSafe>(&mut self, vec: ShmPtr<ShmVec<T>>) -> Result<&mut [T], Error> {
+        let header = self.collection(vec)?;
+        let (data, len) = (header.data(), header.len());
+        if len == 0 {
+            return Ok(&mut []);
+        }
+        self.array_mut(data, len)
+    }
+
+    ///
+    /// Appends the given string to the string (see [push](Self::push)).
+    ///
+    pub fn push_str(&mut self, string: ShmPtr<ShmString>, value: &str) -> Result<(), Error> {
+        let bytes = string
+            .offset()
+            .ok_or(ErrorCode::InvalidAllocation { offset: 0 })?;
+        self.extend_from_slice(ShmPtr::<ShmVec<u8>>::new(bytes), value.as_bytes())
+    }
+
+    ///
+    /// Inserts the given entry in the hash map, returning the previous value of the key.
+    ///
+    /// Values are allocated on their own: the value of a key that is already in the hash map is
+    /// replaced by publishing a pointer to the new value, so that readers never see a partly
+    /// written value, and the previous value is then [retired](Self::retire). When the hash map is
+    /// full, its entries are moved to a table twice as large and the previous table is retired.
+    ///
+    pub fn insert<K: ShmSafe + Hash + Eq, V: ShmSafe>(
+        &mut self,
+        map: ShmPtr<ShmHashMap<K, V>>,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, Error> {
+        let table = self.collection(map)?.table();
+        if let Some(index) = find(buckets(self.as_ref(), table)?, &key) {
+            let value = self.alloc(value)?;
+            let previous = buckets(self.as_ref(), table)?[index]
+                .value
+                .swap(value.to_bits(), Ordering::Release);
+            return self.take(ShmPtr::from_bits(previous)).map(Some);
+        }
+        self.reserve_entry(map)?;
+        let value = self.alloc(value)?;
+        let header = self.collection(map)?;
+        let table = header.table();
+        let buckets = self.buckets_mut(table)?;
+        let index = probe(buckets, &key)
+            .find(|index| buckets[*index].state.load(Ordering::Relaxed) != FULL)
+            .ok_or(ErrorCode::InvalidAllocation {
+                offset: table.offset().unwrap_or(0),
+            })?;
+        let bucket = &mut buckets[index];
+        let previous_state = bucket.state.load(Ordering::Relaxed);
+        unsafe { ptr::write(&mut bucket.key, key) };
+        bucket.value.store(value.to_bits(), Ordering::Relaxed);
+        // Publishes the entry to the readers, after it was written.
+        bucket.state.store(FULL, Ordering::Release);
+        let header = self.collection(map)?;
+        header.len.fetch_add(1, Ordering::Release);
+        if previous_state == EMPTY {
+            header.used.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(None)
+    }
+
+    ///
+    /// Removes the given key from the hash map, returning its value, whose block is
+    /// [retired](Self::retire).
+    ///
+    /// Its bucket is reused by the entries inserted later: readers should not hold on to it.
+    ///
+    pub fn remove<K: ShmSafe + Hash + Eq, V: ShmSafe>(
+        &mut self,
+        map: ShmPtr<ShmHashMap<K, V>>,
+        key: &K,
+    ) -> Result<Option<V>, Error> {
+        let header = self.collection(map)?;
+        let table = header.table();
+        let Some(index) = find(buckets(self.as_ref(), table)?, key) else {
+            return Ok(None);
+        };
+        let buckets = self.buckets_mut(table)?;
+        let value = buckets[index].value();
+        let capacity = buckets.len();
+        let mut emptied = 0;
+        if buckets[(index + 1) % capacity]
+            .state
+            .load(Ordering::Relaxed)
+            == EMPTY
+        {
+            // The probes through the bucket end at the next one: it is emptied instead of being
+            // marked as removed, as are the removed entries before it.
+            let mut current = index;
+            loop {
+                buckets[current].state.store(EMPTY, Ordering::Release);
+                emptied += 1;
+                current = (current + capacity - 1) % capacity;
+                if current == index || buckets[current].state.load(Ordering::Relaxed) != DELETED {
+                    break;
+                }
+            }
+        } else {
+            buckets[index].state.store(DELETED, Ordering::Release);
+        }
+        let header = self.collection(map)?;
+        header.len.fetch_sub(1, Ordering::Release);
+        header.used.fetch_sub(emptied, Ordering::Relaxed);
+        self.take(value).map(Some)
+    }
+
+    ///
+    /// Makes room in the given hash map for one more entry, keeping at least a quarter of its
+    /// buckets empty so that probes end.
+    ///
+    fn reserve_entry<K: ShmSafe + Hash + Eq, V: ShmSafe>(
+        &mut self,
+        map: ShmPtr<ShmHashMap<K, V>>,
+    ) -> Result<(), Error> {
+        let header = self.collection(map)?;
+        let (table, len, used) = (
+            header.table(),
+            header.len(),
+            header.used.load(Ordering::Relaxed) as usize,
+        );
+        let capacity = buckets(self.as_ref(), table)?.len();
+        if (used + 1) * 4 <= capacity * 3 {
+            return Ok(());
+        }
+        // Tables mostly holding removed entries are rebuilt with the same size.
+        let capacity = if (len + 1) * 2 <= capacity {
+            capacity
+        } else {
+            (capacity * 2).max(MIN_BUCKETS)
+        };
+        let new_table = self.alloc_array::<Bucket<K, V>>(capacity + 1)?;
+        self.collection(new_table)?
+            .state
+            .store(capacity as u64, Ordering::Relaxed);
+        // The content of the block may be that of freed values.
+        self.buckets_mut(new_table)?
+            .iter()
+            .for_each(|bucket| bucket.state.store(EMPTY, Ordering::Relaxed));
+        let entries: Vec<usize> = buckets(self.as_ref(), table)?
+            .iter()
+            .enumerate()
+            .filter(|(_, bucket)| bucket.state.load(Ordering::Relaxed) == FULL)
+            .map(|(index, _)| index)
+            .collect();
+        for index in entries {
+            let bucket = &buckets(self.as_ref(), table)?[index];
+            let key = unsafe { ptr::read(&bucket.key) };
+            let value = bucket.value.load(Ordering::Relaxed);
+            let new_buckets = self.buckets_mut(new_table)?;
+            let new_index = probe(new_buckets, &key)
+                .find(|index| new_buckets[*index].state.load(Ordering::Relaxed) == EMPTY)
+                .expect("the new table has empty buckets");
+            let new_bucket = &mut new_buckets[new_index];
+            unsafe { ptr::write(&mut new_bucket.key, key) };
+            new_bucket.value.store(value, Ordering::Relaxed);
+            new_bucket.state.store(FULL, Ordering::Relaxed);
+        }
+        let header = self.collection(map)?;
+        header.table.store(new_table.to_bits(), Ordering::Release);
+        header.used.store(len as u64, Ordering::Relaxed);
+        if !table.is_null() {
+            self.retire(table)?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Copies the value out of its block, and retires the block.
+    ///
+    fn take<T: ShmSafe>(&mut self, ptr: ShmPtr<T>) -> Result<T, Error> {
+        let value = unsafe { ptr::read(self.collection(ptr)?) };
+        self.retire(ptr)?;
+        Ok(value)
+    }
+
+    fn collection<C: ShmSafe>(&self, collection: ShmPtr<C>) -> Result<&C, Error> {
+        collection
+            .get(self)?
+            .ok_or_else(|| ErrorCode::InvalidAllocation { offset: 0 }.into())
+    }
+
+    fn array_mut<T: ShmSafe>(&mut self, data: ShmPtr<T>, len: usize) -> Result<&mut [T], Error> {
+        let offset = data
+            .offset()
+            .ok_or(ErrorCode::InvalidAllocation { offset: 0 })?;
+        let memory = self.as_mut();
+        view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, len)
+            .map(|ptr| unsafe { std::slice::from_raw_parts_mut(ptr as *mut T, len) })
+    }
+
+    fn buckets_mut<K: ShmSafe, V: ShmSafe>(
+        &mut self,
+        table: ShmPtr<Bucket<K, V>>,
+    ) -> Result<&mut [Bucket<K, V>], Error> {
+        let capacity = buckets(self.as_ref(), table)?.len();
+        Ok(&mut self.array_mut(table, capacity + 1)?[1..])
+    }
+}
+
+///
+/// Resolves the buckets of the table against the given memory, past the first one holding
+/// their number.
+///
+fn buckets<K: ShmSafe, V: ShmSafe>(
+    memory: &[u8],
+    table: ShmPtr<Bucket<K, V>>,
+) -> Result<&[Bucket<K, V>], Error> {
+    let Some(offset) = table.offset() else {
+        return Ok(&[]);
+    };
+    let first = view::checked_array::<Bucket<K, V>>(memory.as_ptr(), memory.len(), offset, 1)?;
+    let capacity = unsafe { &*first }.state.load(Ordering::Acquire) as usize;
+    let count = capacity.saturating_add(1);
+    view::checked_array::<Bucket<K, V>>(memory.as_ptr(), memory.len(), offset, count)
+        .map(|ptr| unsafe { &std::slice::from_raw_parts(ptr, count)[1..] })
+}
+
+///
+/// The indexes of the buckets a key may be found in, in order.
+///
+fn probe<K: Hash, V>(buckets: &[Bucket<K, V>], key: &K) -> impl Iterator<Item = usize> {
+    let capacity = buckets.len();
+    let start = match capacity {
+        0 => 0,
+        _ => (hash(key) % capacity as u64) as usize,
+    };
+    (0..capacity).map(move |step| (start + step) % capacity)
+}
+
+///
+/// The index of the bucket holding the given key, None when it is not in the table.
+///
+fn find<K: Hash + Eq, V>(buckets: &[Bucket<K, V>], key: &K) -> Option<usize> {
+    probe(buckets, key)
+        .map(|index| (index, buckets[index].state.load(Ordering::Acquire)))
+        .take_while(|(_, state)| *state != EMPTY)
+        .find(|(index, state)| *state == FULL && buckets[*index].key == *key)
+        .map(|(index, _)| index)
+}
+
+///
+/// Hashes the key with FNV-1a, which gives the same hash in every process.
+///
+fn hash<K: Hash>(key: &K) -> u64 {
+    let mut hasher = FnvHasher(0xcbf2_9ce4_8422_2325);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct FnvHasher(u64);
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::shm::{ErrorCode, ShmDefinition, ShmHashMap, ShmHeap, ShmString, ShmVec};
+
+    #[test]
+    fn push_keeps_the_values_when_the_vector_grows() {
+        let definition = ShmDefinition::new("test_collection1", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let values = heap.alloc(ShmVec::new()).unwrap();
+
+        (0..10u64).for_each(|value| heap.push(values, value).unwrap());
+        heap.slice_mut(values).unwrap()[0] = 10;
+
+        let values = values.get(&heap).unwrap().unwrap();
+        assert_eq!(16, values.capacity());
+        assert_eq!(
+            vec![10, 1, 2, 3, 4, 5, 6, 7, 8, 9],
+            values.resolve(&heap).unwrap().to_vec()
+        );
+    }
+
+    #[test]
+    fn growing_retires_the_previous_block_until_it_is_reclaimed() {
+        let definition = ShmDefinition::new("test_collection7", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let values = heap.alloc(ShmVec::new()).unwrap();
+        heap.extend_from_slice(values, &[1u64, 2, 3, 4]).unwrap();
+        let definition = ShmDefinition::new("test_collection7", NonZero::new(8192).unwrap());
+        let shm = definition.open_read_only().unwrap();
+        let old_values = values.get(&shm).unwrap().unwrap().resolve(&shm).unwrap();
+
+        heap.push(values, 5).unwrap();
+        heap.alloc([9u64; 4]).unwrap();
+
+        assert_eq!([1, 2, 3, 4], old_values[..]);
+        let generation = heap.advance_generation().unwrap();
+        assert_eq!(1, heap.reclaim(generation).unwrap());
+        // The block of the first 4 values and the record of its retirement are reused.
+        let remaining = heap.remaining();
+        heap.alloc([0u64; 4]).unwrap();
+        heap.alloc([0u64; 4]).unwrap();
+        assert_eq!(remaining, heap.remaining());
+        let values = values.get(&heap).unwrap().unwrap();
+        assert_eq!(vec![1, 2, 3, 4, 5], values.resolve(&heap).unwrap().to_vec());
+    }
+
+    #[test]
+    fn resolved_vectors_are_indexed_iterated_and_printed() {
+        let definition = ShmDefinition::new("test_collection8", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let values = heap.alloc(ShmVec::new()).unwrap();
+        heap.extend_from_slice(values, &[1u32, 2, 3]).unwrap();
+
+        let vec = values.get(&heap).unwrap().unwrap();
+        let resolved = vec.resolve(&heap).unwrap();
+
+        assert_eq!(2, resolved[1]);
+        assert_eq!([2, 3], resolved[1..]);
+        assert_eq!(6, (&resolved).into_iter().sum::<u32>());
+        assert_eq!("[1, 2, 3]", format!("{resolved:?}"));
+        assert_eq!(vec![&1, &2, &3], resolved.into_iter().collect::<Vec<_>>());
+        assert_eq!("ShmVec { len: 3, .. }", format!("{vec:?}"));
+    }
+
+    #[test]
+    fn pop_removes_the_last_value() {
+        let definition = ShmDefinition::new("test_collection2", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let values = heap.alloc(ShmVec::new()).unwrap();
+        heap.extend_from_slice(values, &[1u32, 2]).unwrap();
+
+        assert_eq!(Some(2), heap.pop(values).unwrap());
+        assert_eq!(Some(1), heap.pop(values).unwrap());
+        assert_eq!(None, heap.pop(values).unwrap());
+    }
+
+    #[test]
+    fn strings_are_resolved_in_other_maps() {
+        let definition = ShmDefinition::new("test_collection3", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let greeting = heap.alloc(ShmString::new()).unwrap();
+        heap.push_str(greeting, "hello").unwrap();
+        heap.push_str(greeting, ", world").unwrap();
+
+        let definition = ShmDefinition::new("test_collection3", NonZero::new(8192).unwrap());
+        let shm = definition.open_read_only().unwrap();
+        let greeting = greeting.get(&shm).unwrap().unwrap();
+
+        assert_eq!("hello, world", greeting.resolve(&shm).unwrap());
+    }
+
+    #[test]
+    fn resolve_reports_an_error_for_invalid_strings() {
+        let definition = ShmDefinition::new("test_collection4", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let string = heap.alloc(ShmString::new()).unwrap();
+        heap.push_str(string, "ab").unwrap();
+        let bytes = heap
+            .slice_mut(crate::shm::ShmPtr::<ShmVec<u8>>::new(
+                string.offset().unwrap(),
+            ))
+            .unwrap();
+        bytes[1] = 0xff;
+
+        assert_eq!(
+            ErrorCode::InvalidUtf8 { valid_up_to: 1 },
+            string
+                .get(&heap)
+                .unwrap()
+                .unwrap()
+                .resolve(&heap)
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn hash_map_inserts_replaces_and_removes_entries() {
+        let definition = ShmDefinition::new("test_collection5", NonZero::new(65536).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let map = heap.alloc(ShmHashMap::new()).unwrap();
+
+        (0..100u64).for_each(|key| assert_eq!(None, heap.insert(map, key, key * 2).unwrap()));
+        assert_eq!(Some(20), heap.insert(map, 10, 0).unwrap());
+        assert_eq!(Some(4), heap.remove(map, &2).unwrap());
+        assert_eq!(None, heap.remove(map, &2).unwrap());
+
+        let entries = map.get(&heap).unwrap().unwrap().resolve(&heap).unwrap();
+        assert_eq!(99, entries.len());
+        assert_eq!(0, entries[&10]);
+        assert_eq!(Some(&6), entries.get(&3));
+        assert!(!entries.contains_key(&2));
+        let mut keys: Vec<u64> = entries.iter().map(|(key, _)| *key).collect();
+        keys.sort();
+        assert_eq!((0..100).filter(|key| *key != 2).collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    fn hash_map_retires_replaced_and_removed_values() {
+        let definition = ShmDefinition::new("test_collection9", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let map = heap.alloc(ShmHashMap::new()).unwrap();
+        heap.insert(map, 1u32, [0u64; 4]).unwrap();
+        let definition = ShmDefinition::new("test_collection9", NonZero::new(8192).unwrap());
+        let shm = definition.open_read_only().unwrap();
+        let entries = map.get(&shm).unwrap().unwrap().resolve(&shm).unwrap();
+        let old_value = entries.get(&1).unwrap();
+
+        assert_eq!(Some([0; 4]), heap.insert(map, 1, [1; 4]).unwrap());
+        heap.alloc([7u64; 4]).unwrap();
+
+        assert_eq!(&[0; 4], old_value);
+        let generation = heap.advance_generation().unwrap();
+        heap.reclaim(generation).unwrap();
+        let remaining = heap.remaining();
+        for value in 2..100 {
+            assert_eq!(
+                Some([value - 1; 4]),
+                heap.insert(map, 1, [value; 4]).unwrap()
+            );
+            let generation = heap.advance_generation().unwrap();
+            heap.reclaim(generation).unwrap();
+        }
+        assert_eq!(Some([99; 4]), heap.remove(map, &1).unwrap());
+        heap.insert(map, 2, [2; 4]).unwrap();
+        let generation = heap.advance_generation().unwrap();
+        heap.reclaim(generation).unwrap();
+        assert_eq!(remaining, heap.remaining());
+        let entries = map.get(&heap).unwrap().unwrap().resolve(&heap).unwrap();
+        assert_eq!("{2: [2, 2, 2, 2]}", format!("{entries:?}"));
+    }
+
+    #[test]
+    fn removed_entries_do_not_grow_the_hash_map() {
+        let definition = ShmDefinition::new("test_collection6", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let map = heap.alloc(ShmHashMap::new()).unwrap();
+
+        for key in 0..1000u32 {
+            heap.insert(map, key, key).unwrap();
+            heap.remove(map, &key).unwrap();
+            let generation = heap.advance_generation().unwrap();
+            heap.reclaim(generation).unwrap();
+        }
+
+        let entries = map.get(&heap).unwrap().unwrap().resolve(&heap).unwrap();
+        assert!(entries.is_empty());
+        assert_eq!("{}", format!("{entries:?}"));
+    }
+}
@@ -0,0 +1,300 @@
+use std::mem::{align_of, size_of};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::header::layout_fingerprint;
+use super::view::{self, ShmSafe};
+use super::ErrorCode;
+use crate::Error;
+
+/// "rshmdir\0" in ASCII, marking memory starting with a directory.
+const MAGIC: u64 = 0x0072_6964_6d68_7372;
+
+/// The number of bytes reserved for the directory at the start of the memory.
+pub(super) const DIRECTORY_SIZE: usize = 4096;
+/// The maximum length of the names of the objects, in bytes.
+pub const MAX_NAME_LEN: usize = 40;
+const ENTRIES: usize = (DIRECTORY_SIZE - size_of::<DirectoryHeader>()) / size_of::<Entry>();
+
+///
+/// The size, alignment and fingerprint of an object registered in the directory of a shared
+/// memory object (see [OwnedShmMap::register](super::OwnedShmMap::register)).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShmLayout {
+    pub size: usize,
+    pub align: usize,
+    /// Identifies the layout of the object, checked when it is looked up.
+    pub fingerprint: u64,
+}
+
+impl ShmLayout {
+    ///
+    /// The layout of a value of type T, looked up with
+    /// [lookup::\<T\>](super::ShmMapping::lookup).
+    ///
+    pub fn of<T: ShmSafe>() -> Self {
+        ShmLayout {
+            size: size_of::<T>(),
+            align: align_of::<T>(),
+            fingerprint: layout_fingerprint::<T>(),
+        }
+    }
+
+    ///
+    /// The layout of `count` values of type T, looked up with
+    /// [lookup_array::\<T\>](super::ShmMapping::lookup_array).
+    ///
+    pub fn array<T: ShmSafe>(count: usize) -> Self {
+        ShmLayout {
+            size: size_of::<T>().saturating_mul(count),
+            ..Self::of::<T>()
+        }
+    }
+}
+
+#[repr(C)]
+struct DirectoryHeader {
+    magic: AtomicU64,
+    /// The number of registered objects, published after their entry was written.
+    count: AtomicU64,
+    /// The offset of the memory following the last registered object.
+    top: AtomicU64,
+    reserved: [u64; 5],
+}
+
+unsafe impl ShmSafe for DirectoryHeader {}
+
+#[repr(C)]
+struct Entry {
+    /// The name of the object, padded with zeroes.
+    name: [u8; MAX_NAME_LEN],
+    offset: u64,
+    len: u64,
+    fingerprint: u64,
+}
+
+unsafe impl ShmSafe for Entry {}
+
+///
+/// Formats an empty directory in the first [DIRECTORY_SIZE] bytes of the memory.
+///
+pub(super) fn format(memory: &mut [u8]) -> Result<(), Error> {
+    if memory.len() < DIRECTORY_SIZE {
+        return Err(ErrorCode::ViewOutOfBounds {
+            end: DIRECTORY_SIZE,
+            len: memory.len(),
+        }
+        .into());
+    }
+    let header = directory_header(memory)?;
+    header.count.store(0, Ordering::Relaxed);
+    header.top.store(DIRECTORY_SIZE as u64, Ordering::Relaxed);
+    header.magic.store(MAGIC, Ordering::Release);
+    Ok(())
+}
+
+///
+/// Reserves room for an object with the given name and layout in the memory, past the
+/// directory at its start (see [format]), returning its offset.
+///
+pub(super) fn register(memory: &mut [u8], name: &str, layout: &ShmLayout) -> Result<usize, Error> {
+    let padded_name = padded_name(name)?;
+    if find(memory, &padded_name)?.is_some() {
+        return Err(ErrorCode::ObjectAlreadyRegistered(name.to_string()).into());
+    }
+    let header = directory_header(memory)?;
+    let count = header.count.load(Ordering::Relaxed) as usize;
+    if count >= ENTRIES {
+        return Err(ErrorCode::DirectoryFull.into());
+    }
+    let offset =
+        (header.top.load(Ordering::Relaxed) as usize).next_multiple_of(layout.align.max(1));
+    let end = offset.saturating_add(layout.size);
+    if end > memory.len() {
+        return Err(ErrorCode::ViewOutOfBounds {
+            end,
+            len: memory.len(),
+        }
+        .into());
+    }
+    let entry = entries_mut(memory)?;
+    entry[count] = Entry {
+        name: padded_name,
+        offset: offset as u64,
+        len: layout.size as u64,
+        fingerprint: layout.fingerprint,
+    };
+    let header = directory_header(memory)?;
+    header.top.store(end as u64, Ordering::Relaxed);
+    // Publishes the entry to the processes looking it up, after it was written.
+    header.count.store(count as u64 + 1, Ordering::Release);
+    Ok(offset)
+}
+
+///
+/// Finds the object with the given name in the directory at the start of the memory, returning
+/// its offset and length. Its fingerprint must be the given one.
+///
+pub(super) fn lookup(memory: &[u8], name: &str, fingerprint: u64) -> Result<(usize, usize), Error> {
+    let entry = find(memory, &padded_name(name)?)?
+        .ok_or_else(|| ErrorCode::ObjectNotFound(name.to_string()))?;
+    if entry.fingerprint != fingerprint {
+        return Err(ErrorCode::LayoutMismatch {
+            expected: fingerprint,
+            actual: entry.fingerprint,
+        }
+        .into());
+    }
+    Ok((entry.offset as usize, entry.len as usize))
+}
+
+fn find<'a>(memory: &'a [u8], name: &[u8; MAX_NAME_LEN]) -> Result<Option<&'a Entry>, Error> {
+    let header = directory_header(memory)?;
+    if header.magic.load(Ordering::Acquire) != MAGIC {
+        return Err(ErrorCode::DirectoryMissing.into());
+    }
+    let count = (header.count.load(Ordering::Acquire) as usize).min(ENTRIES);
+    let entries = view::checked_array::<Entry>(
+        memory.as_ptr(),
+        memory.len(),
+        size_of::<DirectoryHeader>(),
+        count,
+    )
+    .map(|ptr| unsafe { std::slice::from_raw_parts(ptr, count) })?;
+    Ok(entries.iter().find(|entry| entry.name == *name))
+}
+
+fn directory_header(memory: &[u8]) -> Result<&DirectoryHeader, Error> {
+    view::checked_array::<DirectoryHeader>(memory.as_ptr(), memory.len(), 0, 1)
+        .map(|ptr| unsafe { &*ptr })
+}
+
+fn entries_mut(memory: &mut [u8]) -> Result<&mut [Entry], Error> {
+    view::checked_array::<Entry>(
+        memory.as_ptr(),
+        memory.len(),
+        size_of::<DirectoryHeader>(),
+        ENTRIES,
+    )
+    .map(|ptr| unsafe { std::slice::from_raw_parts_mut(ptr as *mut Entry, ENTRIES) })
+}
+
+///
+/// The name padded with zeroes, which must not be empty, longer than [MAX_NAME_LEN] or hold
+/// zeroes.
+///
+fn padded_name(name: &str) -> Result<[u8; MAX_NAME_LEN], Error> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || name.contains('\0') {
+        return Err(ErrorCode::ObjectNameInvalid(name.to_string()).into());
+    }
+    let mut padded = [0; MAX_NAME_LEN];
+    padded[..name.len()].copy_from_slice(name.as_bytes());
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shm::header::layout_fingerprint;
+    use crate::shm::ErrorCode;
+
+    use super::{format, lookup, register, ShmLayout, DIRECTORY_SIZE, ENTRIES};
+
+    fn memory(len: usize) -> Vec<u64> {
+        vec![0; len / 8]
+    }
+
+    fn bytes(memory: &mut [u64]) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(memory.as_mut_ptr() as *mut u8, memory.len() * 8) }
+    }
+
+    #[test]
+    fn register_places_objects_past_the_directory() {
+        let mut memory = memory(8192);
+        let memory = bytes(&mut memory);
+        format(memory).unwrap();
+
+        let counter = register(memory, "counter", &ShmLayout::of::<u8>()).unwrap();
+        let log = register(memory, "log", &ShmLayout::array::<u64>(4)).unwrap();
+
+        assert_eq!(DIRECTORY_SIZE, counter);
+        assert_eq!(DIRECTORY_SIZE + 8, log);
+        assert_eq!(
+            (log, 32),
+            lookup(memory, "log", layout_fingerprint::<u64>()).unwrap()
+        );
+    }
+
+    #[test]
+    fn lookup_checks_the_name_and_fingerprint() {
+        let mut memory = memory(8192);
+        let memory = bytes(&mut memory);
+        format(memory).unwrap();
+
+        assert_eq!(
+            ErrorCode::ObjectNotFound("counter".to_string()),
+            lookup(memory, "counter", layout_fingerprint::<u64>()).unwrap_err()
+        );
+        register(memory, "counter", &ShmLayout::of::<u64>()).unwrap();
+        assert_eq!(
+            ErrorCode::LayoutMismatch {
+                expected: layout_fingerprint::<u32>(),
+                actual: layout_fingerprint::<u64>()
+            },
+            lookup(memory, "counter", layout_fingerprint::<u32>()).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::ObjectAlreadyRegistered("counter".to_string()),
+            register(memory, "counter", &ShmLayout::of::<u64>()).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::ObjectNameInvalid("".to_string()),
+            register(memory, "", &ShmLayout::of::<u64>()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn register_reports_an_error_when_the_object_does_not_fit() {
+        let mut memory = memory(8192);
+        let memory = bytes(&mut memory);
+        format(memory).unwrap();
+
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: 8193,
+                len: 8192
+            },
+            register(memory, "log", &ShmLayout::array::<u8>(4097)).unwrap_err()
+        );
+        for index in 0..ENTRIES {
+            register(memory, &index.to_string(), &ShmLayout::of::<u8>()).unwrap();
+        }
+        assert_eq!(
+            ErrorCode::DirectoryFull,
+            register(memory, "log", &ShmLayout::of::<u8>()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn register_refuses_memory_without_a_directory() {
+        let mut memory = memory(8192);
+        let memory = bytes(&mut memory);
+        memory[0] = 7;
+
+        assert_eq!(
+            ErrorCode::DirectoryMissing,
+            register(memory, "log", &ShmLayout::of::<u8>()).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::DirectoryMissing,
+            lookup(memory, "log", layout_fingerprint::<u8>()).unwrap_err()
+        );
+        assert_eq!(7, memory[0]);
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: DIRECTORY_SIZE,
+                len: 64
+            },
+            format(&mut memory[..64]).unwrap_err()
+        );
+    }
+}
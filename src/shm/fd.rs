@@ -0,0 +1,163 @@
+use std::mem::{size_of, zeroed};
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use nix::errno::Errno;
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// Sends a file descriptor to the peer of a unix stream (using SCM_RIGHTS).
+///
+/// A single byte is sent along with the file descriptor as ancillary data.
+///
+pub fn send_fd(stream: &UnixStream, fd: BorrowedFd) -> Result<(), Error> {
+    let mut payload = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr() as *mut libc::c_void,
+        iov_len: payload.len(),
+    };
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as u32) } as usize];
+    let mut message: libc::msghdr = unsafe { zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = control.len() as _;
+    unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(size_of::<RawFd>() as u32) as _;
+        (libc::CMSG_DATA(header) as *mut RawFd).write_unaligned(fd.as_raw_fd());
+    }
+    let sent = unsafe { libc::sendmsg(stream.as_raw_fd(), &message, libc::MSG_NOSIGNAL) };
+    Errno::result(sent)
+        .map(|_| ())
+        .map_err(|errno| map_send_error("sendmsg", errno))
+}
+
+///
+/// Receives a file descriptor sent with [send_fd] by the peer of a unix stream.
+///
+/// The received file descriptor is marked close-on-exec. Messages carrying more than a single
+/// file descriptor, which the kernel may have truncated, are refused with a
+/// [ControlMessageTruncated](ErrorCode::ControlMessageTruncated) error and the file
+/// descriptors received are closed.
+///
+pub fn recv_fd(stream: &UnixStream) -> Result<OwnedFd, Error> {
+    let mut payload = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: payload.as_mut_ptr() as *mut libc::c_void,
+        iov_len: payload.len(),
+    };
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(size_of::<RawFd>() as u32) } as usize];
+    let mut message: libc::msghdr = unsafe { zeroed() };
+    message.msg_iov = &mut iov;
+    message.msg_iovlen = 1;
+    message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    message.msg_controllen = control.len() as _;
+    let received =
+        unsafe { libc::recvmsg(stream.as_raw_fd(), &mut message, libc::MSG_CMSG_CLOEXEC) };
+    match Errno::result(received).map_err(|errno| map_send_error("recvmsg", errno))? {
+        0 => Err(ErrorCode::PeerDisconnected.into()),
+        _ => unsafe {
+            let header = libc::CMSG_FIRSTHDR(&message);
+            if header.is_null()
+                || (*header).cmsg_level != libc::SOL_SOCKET
+                || (*header).cmsg_type != libc::SCM_RIGHTS
+            {
+                Err(ErrorCode::NoFileDescriptorReceived.into())
+            } else {
+                let data = libc::CMSG_DATA(header) as *const RawFd;
+                let count =
+                    ((*header).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / size_of::<RawFd>();
+                // Every received file descriptor is owned, so that the unexpected ones are closed.
+                let mut fds: Vec<OwnedFd> = (0..count)
+                    .map(|i| OwnedFd::from_raw_fd(data.add(i).read_unaligned()))
+                    .collect();
+                if fds.len() > 1 || message.msg_flags & libc::MSG_CTRUNC != 0 {
+                    Err(ErrorCode::ControlMessageTruncated.into())
+                } else {
+                    fds.pop().ok_or(ErrorCode::NoFileDescriptorReceived.into())
+                }
+            }
+        },
+    }
+}
+
+fn map_send_error(operation: &'static str, errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EPIPE | Errno::ECONNRESET | Errno::ENOTCONN => ErrorCode::PeerDisconnected,
+        other => ErrorCode::Unknown(other),
+    };
+    Error::from_errno(code, operation, errno)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Seek, Write};
+    use std::mem::{size_of_val, zeroed};
+    use std::os::fd::{AsFd, AsRawFd, RawFd};
+    use std::os::unix::net::UnixStream;
+
+    use crate::shm::ErrorCode;
+
+    use super::{recv_fd, send_fd};
+
+    #[test]
+    fn recv_fd_returns_a_file_descriptor_sent_with_send_fd() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let mut file = std::fs::File::from(
+            nix::sys::memfd::memfd_create("test_fd", nix::sys::memfd::MFdFlags::empty()).unwrap(),
+        );
+        file.write_all(b"rshm").unwrap();
+
+        send_fd(&sender, file.as_fd()).unwrap();
+        let mut received = std::fs::File::from(recv_fd(&receiver).unwrap());
+        let mut content = String::new();
+        received.rewind().unwrap();
+        received.read_to_string(&mut content).unwrap();
+
+        assert_eq!("rshm", content);
+    }
+
+    #[test]
+    fn recv_fd_reports_an_error_when_several_file_descriptors_are_sent() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let fds = [sender.as_raw_fd(), receiver.as_raw_fd(), sender.as_raw_fd()];
+        let mut payload = [0u8; 1];
+        let mut iov = libc::iovec {
+            iov_base: payload.as_mut_ptr() as *mut libc::c_void,
+            iov_len: payload.len(),
+        };
+        let fds_len = size_of_val(&fds) as u32;
+        let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len) } as usize];
+        let mut message: libc::msghdr = unsafe { zeroed() };
+        message.msg_iov = &mut iov;
+        message.msg_iovlen = 1;
+        message.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        message.msg_controllen = control.len() as _;
+        unsafe {
+            let header = libc::CMSG_FIRSTHDR(&message);
+            (*header).cmsg_level = libc::SOL_SOCKET;
+            (*header).cmsg_type = libc::SCM_RIGHTS;
+            (*header).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+            (libc::CMSG_DATA(header) as *mut [RawFd; 3]).write_unaligned(fds);
+            assert_eq!(1, libc::sendmsg(sender.as_raw_fd(), &message, 0));
+        }
+
+        assert_eq!(
+            ErrorCode::ControlMessageTruncated,
+            recv_fd(&receiver).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn recv_fd_reports_an_error_when_the_peer_is_gone() {
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        drop(sender);
+
+        assert_eq!(ErrorCode::PeerDisconnected, recv_fd(&receiver).unwrap_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::num::NonZero;
+use std::ptr::NonNull;
+
+use libc::c_void;
+use nix::errno::Errno;
+use nix::sys::mman::{mmap_anonymous, mremap, munmap, MRemapFlags, MapFlags, ProtFlags};
+
+///
+/// Reserves the address space for a mapping of `size` bytes surrounded by `guard` bytes of
+/// PROT_NONE pages, returning the address of the mapping, aligned on `guard` bytes.
+///
+/// The mapping is then placed at the returned address with MAP_FIXED, replacing the reserved
+/// pages it covers.
+///
+pub(super) fn reserve(size: usize, guard: usize) -> Result<NonNull<c_void>, Errno> {
+    let mapped = size.next_multiple_of(guard);
+    // A guard more is reserved, so that the mapping can be aligned on guard bytes.
+    let reserved = mapped + 3 * guard;
+    let start = unsafe {
+        mmap_anonymous(
+            None,
+            NonZero::new(reserved).ok_or(Errno::EINVAL)?,
+            ProtFlags::PROT_NONE,
+            MapFlags::MAP_PRIVATE | MapFlags::MAP_NORESERVE,
+        )
+    }?;
+    let aligned = (start.as_ptr() as usize).next_multiple_of(guard);
+    let head = aligned + guard;
+    let end = start.as_ptr() as usize + reserved;
+    unmap(start.as_ptr() as usize, aligned - start.as_ptr() as usize)
+        .and_then(|_| unmap(head + mapped + guard, end - (head + mapped + guard)))
+        .inspect_err(|_| {
+            let _unmap_result = unmap(start.as_ptr() as usize, reserved);
+        })?;
+    NonNull::new(head as *mut c_void).ok_or(Errno::EINVAL)
+}
+
+///
+/// Unmaps the mapping of `size` bytes at `head` and its guard pages.
+///
+pub(super) fn release(head: NonNull<c_void>, size: usize, guard: usize) -> Result<(), Errno> {
+    let mapped = if guard == 0 {
+        size
+    } else {
+        size.next_multiple_of(guard)
+    };
+    unmap(head.as_ptr() as usize - guard, mapped + 2 * guard)
+}
+
+///
+/// Extends the mapping of `size` bytes at `head` to `new_size` bytes, moving it to a new
+/// reservation with its guard pages.
+///
+pub(super) fn remap(
+    head: NonNull<c_void>,
+    size: usize,
+    new_size: usize,
+    guard: usize,
+) -> Result<NonNull<c_void>, Errno> {
+    let new_head = reserve(new_size, guard)?;
+    let moved = unsafe {
+        mremap(
+            head,
+            size,
+            new_size,
+            MRemapFlags::MREMAP_MAYMOVE | MRemapFlags::MREMAP_FIXED,
+            Some(new_head),
+        )
+    }
+    .inspect_err(|_| {
+        let _release_result = release(new_head, new_size, guard);
+    })?;
+    // The mapping moved out of its previous reservation: only its guard pages remain there.
+    let head = head.as_ptr() as usize;
+    unmap(head - guard, guard)
+        .and_then(|_| unmap(head + size.next_multiple_of(guard), guard))
+        .map(|_| moved)
+}
+
+fn unmap(address: usize, len: usize) -> Result<(), Errno> {
+    match NonNull::new(address as *mut c_void) {
+        Some(address) if len > 0 => unsafe { munmap(address, len) },
+        _ => Ok(()),
+    }
+}
+
+///
+/// The permissions of the mapping holding the given address, as shown in /proc/self/maps
+/// (e.g. "---p" for a guard page).
+///
+#[cfg(test)]
+pub(super) fn permissions(address: usize) -> Option<String> {
+    std::fs::read_to_string("/proc/self/maps")
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let (range, rest) = line.split_once(' ')?;
+            let (start, end) = range.split_once('-')?;
+            let start = usize::from_str_radix(start, 16).ok()?;
+            let end = usize::from_str_radix(end, 16).ok()?;
+            (start..end)
+                .contains(&address)
+                .then(|| rest[..4].to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{permissions, release, reserve};
+
+    #[test]
+    fn reserve_surrounds_the_mapping_with_guard_pages() {
+        let head = reserve(8192, 4096).unwrap();
+        let address = head.as_ptr() as usize;
+
+        assert_eq!(Some("---p".to_string()), permissions(address - 4096));
+        assert_eq!(Some("---p".to_string()), permissions(address + 8192));
+        assert_eq!(0, address % 4096);
+        release(head, 8192, 4096).unwrap();
+    }
+
+    #[test]
+    fn reserve_aligns_the_mapping_on_the_guard_size() {
+        let guard = 2 * 1024 * 1024;
+        let head = reserve(guard, guard).unwrap();
+
+        assert_eq!(0, head.as_ptr() as usize % guard);
+        release(head, guard, guard).unwrap();
+    }
+}
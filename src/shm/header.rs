@@ -0,0 +1,508 @@
+use std::mem::{align_of, size_of};
+use std::num::NonZero;
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::condvar::{futex_wait, futex_wake_all};
+
+use super::list::HeaderInfo;
+use super::owner::{is_alive, process_id, process_start_time};
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// The number of bytes reserved for the header at the start of a shared memory object.
+/// It spans a whole page so that the memory following the header stays page aligned.
+///
+pub const HEADER_SIZE: usize = 4096;
+
+/// Identifies shared memory objects that start with an rshm header.
+const MAGIC: u64 = u64::from_le_bytes(*b"rshm_seg");
+
+/// The owner of an object that was released by its creator and not adopted yet.
+const NO_OWNER: u64 = 0;
+
+/// The version of the header format, incremented when the header changes.
+pub const HEADER_VERSION: u32 = 2;
+
+/// The object was truncated but its header was not written yet (memory is zero filled).
+const STATE_UNINITIALIZED: i32 = 0;
+/// The header was written by the owner, which is initializing the rest of the memory.
+const STATE_CREATED: i32 = 1;
+/// The owner marked the shared memory object as initialized.
+const STATE_INITIALIZED: i32 = 2;
+
+///
+/// The header written at the start of a shared memory object created with a header.
+///
+#[repr(C)]
+pub(super) struct Header {
+    magic: AtomicU64,
+    /// Used as a futex word by processes waiting for the object to be initialized.
+    state: AtomicI32,
+    /// The [HEADER_VERSION] of the process that created the object.
+    version: AtomicU32,
+    /// Incremented each time the owner grows the object.
+    generation: AtomicU64,
+    /// The total size of the object, header included.
+    size: AtomicU64,
+    /// The offset of the memory following the header.
+    payload_offset: AtomicU64,
+    /// Identifies the layout of the memory following the header, 0 when not given.
+    fingerprint: AtomicU64,
+    /// The creation time of the object, in nanoseconds since the unix epoch.
+    created: AtomicU64,
+    /// The process responsible for unlinking the object, 0 when none is: its process id in the
+    /// low 32 bits, and its start time (see [process_start_time]) truncated to 32 bits in the
+    /// high ones, so that both always change together.
+    owner: AtomicU64,
+}
+
+impl Header {
+    ///
+    /// Views the header at the start of the mapped memory.
+    ///
+    /// # Safety
+    /// `head` must point to a mapping of at least [HEADER_SIZE] bytes that outlives the header.
+    ///
+    pub(super) unsafe fn at<'a>(head: *const u8) -> &'a Header {
+        unsafe { &*(head as *const Header) }
+    }
+
+    ///
+    /// Writes the header of a newly created shared memory object.
+    ///
+    pub(super) fn write_created(&self, size: NonZero<usize>, fingerprint: Option<u64>) {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        self.magic.store(MAGIC, Ordering::Relaxed);
+        self.version.store(HEADER_VERSION, Ordering::Relaxed);
+        self.size.store(size.get() as u64, Ordering::Relaxed);
+        self.payload_offset
+            .store(HEADER_SIZE as u64, Ordering::Relaxed);
+        self.fingerprint
+            .store(fingerprint.unwrap_or(0), Ordering::Relaxed);
+        self.created.store(created, Ordering::Relaxed);
+        self.owner.store(own_owner(), Ordering::Relaxed);
+        self.state.store(STATE_CREATED, Ordering::Release);
+    }
+
+    ///
+    /// Publishes the initialization of the memory following the header and wakes waiting processes.
+    ///
+    pub(super) fn mark_initialized(&self) {
+        self.state.store(STATE_INITIALIZED, Ordering::Release);
+        futex_wake_all(&self.state);
+    }
+
+    ///
+    /// The number of times the shared memory object was grown.
+    ///
+    pub(super) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    ///
+    /// Publishes a new size of the shared memory object to the processes mapping it.
+    ///
+    pub(super) fn publish_size(&self, size: NonZero<usize>) {
+        self.size.store(size.get() as u64, Ordering::Relaxed);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    ///
+    /// Gives up the responsibility of unlinking the shared memory object, which then
+    /// outlives the process until it is adopted.
+    ///
+    pub(super) fn release_ownership(&self) {
+        let owner = self.owner.load(Ordering::Acquire);
+        if owner_id(owner) == process_id() {
+            let _released =
+                self.owner
+                    .compare_exchange(owner, NO_OWNER, Ordering::AcqRel, Ordering::Acquire);
+        }
+    }
+
+    ///
+    /// Takes the responsibility of unlinking the shared memory object if no process has it,
+    /// otherwise returns the process id of its owner.
+    ///
+    pub(super) fn acquire_ownership(&self) -> Result<(), Error> {
+        self.take_ownership_from(NO_OWNER)
+    }
+
+    ///
+    /// Takes the responsibility of unlinking the shared memory object if its owner is dead,
+    /// otherwise returns the process id of its owner. Objects without owner are not reclaimed:
+    /// they wait to be adopted (see [acquire_ownership](Header::acquire_ownership)).
+    ///
+    pub(super) fn reclaim_ownership(&self) -> Result<(), Error> {
+        let owner = self.owner.load(Ordering::Acquire);
+        match self.owner() {
+            _ if owner == NO_OWNER => Err(ErrorCode::Unowned.into()),
+            (pid, start_time) if is_alive(pid, start_time) => {
+                Err(ErrorCode::AlreadyOwned { pid }.into())
+            }
+            _ => self.take_ownership_from(owner),
+        }
+    }
+
+    fn take_ownership_from(&self, owner: u64) -> Result<(), Error> {
+        // The id and start time of the owner are replaced at once.
+        self.owner
+            .compare_exchange(owner, own_owner(), Ordering::AcqRel, Ordering::Acquire)
+            .map(|_| ())
+            .map_err(|actual| match actual {
+                NO_OWNER => ErrorCode::Unowned.into(),
+                actual => ErrorCode::AlreadyOwned {
+                    pid: owner_id(actual),
+                }
+                .into(),
+            })
+    }
+
+    ///
+    /// The process id and truncated start time (0 when unknown) of the owner of the shared memory
+    /// object.
+    ///
+    pub(super) fn owner(&self) -> (i32, u32) {
+        let owner = self.owner.load(Ordering::Acquire);
+        (owner_id(owner), (owner >> 32) as u32)
+    }
+
+    #[cfg(test)]
+    pub(super) fn set_owner(&self, pid: i32) {
+        self.owner.store(pid as u32 as u64, Ordering::Release);
+    }
+
+    ///
+    /// Whether the owner of the shared memory object died without unlinking it.
+    /// Objects without owner (see [release_ownership](Header::release_ownership)) are not stale.
+    ///
+    pub(super) fn is_stale(&self) -> bool {
+        let (pid, start_time) = self.owner();
+        self.owner.load(Ordering::Acquire) != NO_OWNER && !is_alive(pid, start_time)
+    }
+
+    ///
+    /// The creation time of the shared memory object.
+    ///
+    pub(super) fn created(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.created.load(Ordering::Relaxed))
+    }
+
+    ///
+    /// Waits until the owner marks the shared memory object as initialized.
+    ///
+    pub(super) fn wait_initialized(&self, deadline: Instant) -> Result<(), Error> {
+        loop {
+            let state = self.state.load(Ordering::Acquire);
+            if state == STATE_INITIALIZED {
+                return Ok(());
+            }
+            if state != STATE_UNINITIALIZED && state != STATE_CREATED {
+                return Err(ErrorCode::InvalidHeader.into());
+            }
+            let remaining = deadline
+                .checked_duration_since(Instant::now())
+                .ok_or(ErrorCode::WaitTimedOut)?;
+            futex_wait(&self.state, state, remaining);
+        }
+    }
+
+    ///
+    /// Describes the header, if it was written by rshm.
+    ///
+    pub(super) fn info(&self) -> Option<HeaderInfo> {
+        let state = self.state.load(Ordering::Acquire);
+        if state == STATE_UNINITIALIZED || self.magic.load(Ordering::Relaxed) != MAGIC {
+            return None;
+        }
+        let (owner, _) = self.owner();
+        let owned = self.owner.load(Ordering::Acquire) != NO_OWNER;
+        Some(HeaderInfo {
+            version: self.version.load(Ordering::Relaxed),
+            size: self.size.load(Ordering::Relaxed),
+            payload_offset: self.payload_offset.load(Ordering::Relaxed),
+            fingerprint: self.fingerprint.load(Ordering::Relaxed),
+            created: self.created(),
+            generation: self.generation(),
+            initialized: state == STATE_INITIALIZED,
+            owner: owned.then_some(owner),
+            stale: self.is_stale(),
+        })
+    }
+
+    ///
+    /// Checks that the header was written by a compatible process, for an object of `size` bytes
+    /// holding the layout of the given fingerprint (if any).
+    ///
+    /// The size in the header can be lower than the size of the object while its owner grows it.
+    ///
+    pub(super) fn check(
+        &self,
+        size: NonZero<usize>,
+        fingerprint: Option<u64>,
+    ) -> Result<(), Error> {
+        if self.state.load(Ordering::Acquire) == STATE_UNINITIALIZED
+            || self.magic.load(Ordering::Relaxed) != MAGIC
+        {
+            return Err(ErrorCode::InvalidHeader.into());
+        }
+        let version = self.version.load(Ordering::Relaxed);
+        if version != HEADER_VERSION {
+            return Err(ErrorCode::HeaderVersionMismatch {
+                expected: HEADER_VERSION,
+                actual: version,
+            }
+            .into());
+        }
+        let payload_offset = self.payload_offset.load(Ordering::Relaxed) as usize;
+        if payload_offset != HEADER_SIZE {
+            return Err(ErrorCode::PayloadOffsetMismatch {
+                expected: HEADER_SIZE,
+                actual: payload_offset,
+            }
+            .into());
+        }
+        let header_size = self.size.load(Ordering::Relaxed) as usize;
+        if header_size > size.get() {
+            return Err(ErrorCode::SizeMismatch {
+                expected: size.get(),
+                actual: header_size,
+            }
+            .into());
+        }
+        match (fingerprint, self.fingerprint.load(Ordering::Relaxed)) {
+            (Some(expected), actual) if expected != actual => {
+                Err(ErrorCode::LayoutMismatch { expected, actual }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+///
+/// The owner word recording this process (see [Header::owner]).
+///
+fn own_owner() -> u64 {
+    let start_time = process_start_time(process_id()).map_or(0, |start_time| start_time as u32);
+    (start_time as u64) << 32 | process_id() as u32 as u64
+}
+
+fn owner_id(owner: u64) -> i32 {
+    owner as u32 as i32
+}
+
+///
+/// The fingerprint of the layout of T: a FNV-1a hash of its name, size and alignment.
+///
+/// The name is taken without module paths, so that programs defining the same record type
+/// in different modules or crates agree on its fingerprint. Type names are not guaranteed to be
+/// stable across compiler versions, so processes sharing an object should be built with the
+/// same toolchain.
+///
+pub(super) fn layout_fingerprint<T>() -> u64 {
+    type_name_without_paths(std::any::type_name::<T>())
+        .bytes()
+        .chain((size_of::<T>() as u64).to_le_bytes())
+        .chain((align_of::<T>() as u64).to_le_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Removes the module paths from a type name (e.g. "alloc::vec::Vec<u8>" becomes "Vec<u8>").
+fn type_name_without_paths(name: &str) -> String {
+    let mut stripped = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(separator) = rest.find("::") {
+        let segment = &rest[..separator];
+        let path_start = segment
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        stripped.push_str(&segment[..path_start]);
+        rest = &rest[separator + 2..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, Instant};
+
+    use crate::shm::ErrorCode;
+
+    use super::{layout_fingerprint, type_name_without_paths, Header, HEADER_SIZE, HEADER_VERSION};
+
+    const SIZE: NonZero<usize> = NonZero::new(8192).expect("8192 is not zero");
+
+    #[test]
+    fn wait_initialized_times_out_when_the_header_is_not_initialized() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+
+        let error = header
+            .wait_initialized(Instant::now() + Duration::from_millis(10))
+            .unwrap_err();
+
+        assert_eq!(ErrorCode::WaitTimedOut, error);
+    }
+
+    #[test]
+    fn wait_initialized_returns_once_the_header_is_initialized() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+        header.mark_initialized();
+
+        header
+            .wait_initialized(Instant::now() + Duration::from_millis(10))
+            .unwrap();
+    }
+
+    #[test]
+    fn check_accepts_the_header_of_a_created_object() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, Some(layout_fingerprint::<u64>()));
+
+        header
+            .check(SIZE, Some(layout_fingerprint::<u64>()))
+            .unwrap();
+        header.check(SIZE, None).unwrap();
+    }
+
+    #[test]
+    fn check_reports_an_error_when_the_header_is_not_written() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+
+        assert_eq!(
+            ErrorCode::InvalidHeader,
+            header.check(SIZE, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn check_reports_an_error_when_the_version_differs() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+        header.version.store(HEADER_VERSION + 1, Ordering::Relaxed);
+
+        assert_eq!(
+            ErrorCode::HeaderVersionMismatch {
+                expected: HEADER_VERSION,
+                actual: HEADER_VERSION + 1
+            },
+            header.check(SIZE, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn check_reports_an_error_when_the_payload_offset_differs() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+        header.payload_offset.store(64, Ordering::Relaxed);
+
+        assert_eq!(
+            ErrorCode::PayloadOffsetMismatch {
+                expected: HEADER_SIZE,
+                actual: 64
+            },
+            header.check(SIZE, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn check_reports_an_error_when_the_object_is_smaller_than_its_header_says() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+
+        assert_eq!(
+            ErrorCode::SizeMismatch {
+                expected: 4097,
+                actual: 8192
+            },
+            header
+                .check(NonZero::new(4097).expect("4097 is not zero"), None)
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn check_reports_an_error_when_the_layout_differs() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, Some(layout_fingerprint::<u64>()));
+
+        assert_eq!(
+            ErrorCode::LayoutMismatch {
+                expected: layout_fingerprint::<i64>(),
+                actual: layout_fingerprint::<u64>()
+            },
+            header
+                .check(SIZE, Some(layout_fingerprint::<i64>()))
+                .unwrap_err()
+        );
+    }
+
+    #[test]
+    fn type_name_without_paths_keeps_the_last_segment_of_each_path() {
+        assert_eq!(
+            "Vec<Option<(u8, Record)>>",
+            type_name_without_paths("alloc::vec::Vec<core::option::Option<(u8, log::Record)>>")
+        );
+    }
+
+    #[test]
+    fn acquire_ownership_succeeds_once_the_owner_released_it() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+
+        assert_eq!(
+            ErrorCode::AlreadyOwned {
+                pid: std::process::id() as i32
+            },
+            header.acquire_ownership().unwrap_err()
+        );
+        header.release_ownership();
+        assert_eq!(ErrorCode::Unowned, header.reclaim_ownership().unwrap_err());
+        header.acquire_ownership().unwrap();
+    }
+
+    #[test]
+    fn reclaim_ownership_succeeds_once_the_owner_is_dead() {
+        let memory = vec![0u64; HEADER_SIZE / 8];
+        let header = unsafe { Header::at(memory.as_ptr() as *const u8) };
+        header.write_created(SIZE, None);
+        assert!(!header.is_stale());
+        assert_eq!(
+            ErrorCode::AlreadyOwned {
+                pid: std::process::id() as i32
+            },
+            header.reclaim_ownership().unwrap_err()
+        );
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id() as i32;
+        child.wait().unwrap();
+        header.set_owner(dead_pid);
+
+        assert!(header.is_stale());
+        header.reclaim_ownership().unwrap();
+        assert_eq!(std::process::id() as i32, header.owner().0);
+        assert!(!header.is_stale());
+    }
+}
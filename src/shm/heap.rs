@@ -0,0 +1,633 @@
+use std::mem::{align_of, size_of};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::view::{self, ShmSafe};
+use super::{ErrorCode, ShmPtr};
+use crate::Error;
+
+/// "rshmheap" in ASCII, marking memory formatted as a heap.
+const MAGIC: u64 = 0x7061_6568_6d68_7372;
+
+/// The number of size classes: blocks of 2^class bytes, from 32 bytes.
+const CLASSES: usize = 48;
+const MIN_CLASS: u32 = 5;
+
+/// Blocks start with a tag and the offset of the next free block, and are aligned on 16 bytes,
+/// as their payload.
+const BLOCK_HEADER: usize = 16;
+const BLOCK_ALIGN: usize = 16;
+
+/// "blck" in ASCII, in the upper half of the tag of a block, next to its size class.
+const BLOCK_MAGIC: u64 = 0x6b63_6c62 << 32;
+
+/// Set in the tag of free blocks, so that they are not freed twice.
+const FREE: u64 = 1 << 31;
+/// Set in the tag of retired blocks, which are freed when they are reclaimed.
+const RETIRED: u64 = 1 << 30;
+/// The bits of the tag holding the size class.
+const CLASS_MASK: u64 = 0xff;
+
+///
+/// The header of a block, before its payload.
+///
+#[repr(C)]
+struct BlockHeader {
+    /// [BLOCK_MAGIC], the size class, and [FREE] or [RETIRED] when the block is free or retired.
+    tag: AtomicU64,
+    /// The offset of the payload of the next free block of the class, 0 for the last one.
+    next: AtomicU64,
+}
+
+unsafe impl ShmSafe for BlockHeader {}
+
+///
+/// The metadata of a heap, at the start of its memory.
+///
+#[repr(C)]
+struct HeapHeader {
+    magic: AtomicU64,
+    /// The offset of the memory that was never allocated.
+    top: AtomicU64,
+    /// The offset of the first free block of each size class, 0 when there is none.
+    free: [AtomicU64; CLASSES],
+    /// The generation of the blocks retired from now on.
+    generation: AtomicU64,
+    /// The offset of the record of the last retired block, 0 when there is none.
+    retired: AtomicU64,
+}
+
+unsafe impl ShmSafe for HeapHeader {}
+
+///
+/// The record of a retired block, allocated in the heap. Records are linked from the last
+/// retired block to the first one, so that their generations decrease along the list.
+///
+#[repr(C)]
+struct Retired {
+    /// The offset of the payload of the retired block.
+    block: u64,
+    /// The generation the block was retired in.
+    generation: u64,
+    /// The offset of the record of the block retired before it, 0 for the first one.
+    next: u64,
+}
+
+unsafe impl ShmSafe for Retired {}
+
+/// The offset of the first block, past the metadata.
+const FIRST_BLOCK: usize = size_of::<HeapHeader>().next_multiple_of(BLOCK_ALIGN);
+
+///
+/// An allocator managing the memory of a mapped shared memory object (past its header, if any),
+/// e.g. to store strings or nested vectors in it.
+///
+/// Allocations are returned as [ShmPtr], which other processes resolve against their own map.
+/// Memory is allocated in blocks of a power of two bytes, and freed blocks are reused for
+/// allocations of the same size class. All the metadata of the heap is stored in the object
+/// as offsets, so that another process can take it over (e.g. after [adopting](super::ShmMap::adopt)
+/// the object) with [open](ShmHeap::open).
+///
+/// A single process allocates at any time, while many read the allocated values. Values are
+/// published to readers by storing their pointer with a release ordering (e.g. in an
+/// [AtomicU64]), and are only freed once no reader can reach them anymore. Values that readers
+/// may still be reading (e.g. the storage replaced when a [ShmVec](super::ShmVec) grows) are
+/// [retired](ShmHeap::retire) instead: they are kept from reuse until the owner
+/// [reclaims](ShmHeap::reclaim) them, once the readers are done with them.
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use rshm::shm::{ShmDefinition, ShmHeap, ShmPtr};
+///
+/// let definition = ShmDefinition::new("example_heap", std::num::NonZero::new(8192).unwrap());
+/// let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+/// let root = heap.alloc(AtomicU64::new(0)).unwrap();
+/// let value = heap.alloc(42u64).unwrap();
+/// root.get(&heap).unwrap().unwrap().store(value.offset().unwrap() as u64, Ordering::Release);
+///
+/// let definition = ShmDefinition::new("example_heap", std::num::NonZero::new(8192).unwrap());
+/// let shm = definition.open_read_only().unwrap();
+/// let offset = root.get(&shm).unwrap().unwrap().load(Ordering::Acquire);
+/// assert_eq!(Some(&42), ShmPtr::<u64>::new(offset as usize).get(&shm).unwrap());
+/// ```
+///
+#[derive(Debug)]
+pub struct ShmHeap<M> {
+    map: M,
+}
+
+impl<M: AsRef<[u8]> + AsMut<[u8]>> ShmHeap<M> {
+    ///
+    /// Formats the memory of the given map as an empty heap.
+    ///
+    pub fn create(map: M) -> Result<Self, Error> {
+        if map.as_ref().len() < FIRST_BLOCK {
+            return Err(ErrorCode::HeapExhausted { size: FIRST_BLOCK }.into());
+        }
+        let heap = ShmHeap { map };
+        let header = heap.header()?;
+        header.top.store(FIRST_BLOCK as u64, Ordering::Relaxed);
+        header
+            .free
+            .iter()
+            .for_each(|free| free.store(0, Ordering::Relaxed));
+        header.generation.store(0, Ordering::Relaxed);
+        header.retired.store(0, Ordering::Relaxed);
+        header.magic.store(MAGIC, Ordering::Release);
+        Ok(heap)
+    }
+
+    ///
+    /// Takes over the heap formatted in the memory of the given map, e.g. by a process that
+    /// gave up the object.
+    ///
+    pub fn open(map: M) -> Result<Self, Error> {
+        let heap = ShmHeap { map };
+        let header = heap.header()?;
+        let top = header.top.load(Ordering::Acquire) as usize;
+        if header.magic.load(Ordering::Acquire) != MAGIC
+            || !(FIRST_BLOCK..=heap.map.as_ref().len()).contains(&top)
+        {
+            return Err(ErrorCode::InvalidHeap.into());
+        }
+        Ok(heap)
+    }
+
+    ///
+    /// Allocates a block for the given value and moves it in.
+    ///
+    pub fn alloc<T: ShmSafe>(&mut self, value: T) -> Result<ShmPtr<T>, Error> {
+        let ptr = self.alloc_array::<T>(1)?;
+        let slot = ptr.get_mut(&mut self.map)?.ok_or(ErrorCode::InvalidHeap)?;
+        // The block may hold the bytes of a freed value, which must not be dropped.
+        unsafe { std::ptr::write(slot, value) };
+        Ok(ptr)
+    }
+
+    ///
+    /// Allocates a block for `count` values of type T, returning a pointer to the first one.
+    ///
+    /// The content of the block is left as is: it may hold the values of a freed block.
+    ///
+    pub fn alloc_array<T: ShmSafe>(&mut self, count: usize) -> Result<ShmPtr<T>, Error> {
+        if align_of::<T>() > BLOCK_ALIGN {
+            return Err(ErrorCode::AlignmentUnsupported {
+                align: align_of::<T>(),
+            }
+            .into());
+        }
+        let size = size_of::<T>()
+            .checked_mul(count)
+            .ok_or(ErrorCode::HeapExhausted { size: usize::MAX })?;
+        self.alloc_bytes(size).map(ShmPtr::new)
+    }
+
+    ///
+    /// Frees the block allocated for the given pointer, so that it is reused by later allocations.
+    ///
+    /// The pointer is checked to point to a block by walking the blocks allocated before it, so
+    /// that a pointer into the payload of a block is never freed, even when the payload looks
+    /// like the header of a block.
+    ///
+    pub fn free<T>(&mut self, ptr: ShmPtr<T>) -> Result<(), Error> {
+        let (offset, class) = self.allocated_block(ptr, 0)?;
+        self.release(offset, class)
+    }
+
+    ///
+    /// Retires the block allocated for the given pointer, which readers may still be reading
+    /// (e.g. a value replaced in a hash map): it is left as is until it is
+    /// [reclaimed](Self::reclaim), and then freed.
+    ///
+    /// The block is retired in the current [generation](Self::generation). Retiring allocates a
+    /// record of the block in the heap, freed along with it.
+    ///
+    pub fn retire<T>(&mut self, ptr: ShmPtr<T>) -> Result<(), Error> {
+        let (offset, class) = self.allocated_block(ptr, 0)?;
+        let header = self.header()?;
+        let record = Retired {
+            block: offset as u64,
+            generation: header.generation.load(Ordering::Relaxed),
+            next: header.retired.load(Ordering::Relaxed),
+        };
+        let record = self.alloc(record)?.offset().unwrap_or(0);
+        self.at::<BlockHeader>(offset - BLOCK_HEADER)?
+            .tag
+            .store(BLOCK_MAGIC | RETIRED | class as u64, Ordering::Relaxed);
+        self.header()?
+            .retired
+            .store(record as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// returns the generation of the blocks retired from now on
+    pub fn generation(&self) -> u64 {
+        self.header()
+            .map(|header| header.generation.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    ///
+    /// Starts a new generation of retired blocks, and returns it.
+    ///
+    /// Once every reader has let go of the values it resolved before the new generation started
+    /// (e.g. readers acknowledge it by storing it in the object), the blocks retired before it
+    /// are no longer read and can be [reclaimed](Self::reclaim).
+    ///
+    pub fn advance_generation(&mut self) -> Result<u64, Error> {
+        let header = self.header()?;
+        let generation = header.generation.load(Ordering::Relaxed) + 1;
+        header.generation.store(generation, Ordering::Relaxed);
+        Ok(generation)
+    }
+
+    ///
+    /// Frees the blocks retired before the given generation (see
+    /// [advance_generation](Self::advance_generation)), returning their number.
+    ///
+    pub fn reclaim(&mut self, generation: u64) -> Result<usize, Error> {
+        // The records are linked from the last retired block: the blocks to free follow the
+        // last record of the given generation or a later one.
+        let header = self.header()?;
+        // The records are blocks: there are fewer than the smallest blocks fitting in the heap.
+        let mut records = header.top.load(Ordering::Relaxed) as usize >> MIN_CLASS;
+        let mut previous = None;
+        let mut current = header.retired.load(Ordering::Relaxed) as usize;
+        while current != 0 {
+            let record = self.at::<Retired>(current)?;
+            if record.generation < generation {
+                break;
+            }
+            records = records.checked_sub(1).ok_or(ErrorCode::InvalidHeap)?;
+            previous = Some(current);
+            current = record.next as usize;
+        }
+        match previous {
+            Some(previous) => self.record_mut(previous)?.next = 0,
+            None => self.header()?.retired.store(0, Ordering::Relaxed),
+        }
+        let mut reclaimed = 0;
+        while current != 0 {
+            let record = self.at::<Retired>(current)?;
+            let (block, next) = (record.block as usize, record.next as usize);
+            let (block, class) = self.allocated_block(ShmPtr::<u8>::new(block), RETIRED)?;
+            self.release(block, class)?;
+            self.free(ShmPtr::<Retired>::new(current))?;
+            reclaimed += 1;
+            current = next;
+        }
+        Ok(reclaimed)
+    }
+
+    /// returns the number of bytes that were never allocated, at the end of the memory
+    pub fn remaining(&self) -> usize {
+        self.header()
+            .map(|header| self.map.as_ref().len() - header.top.load(Ordering::Relaxed) as usize)
+            .unwrap_or(0)
+    }
+
+    /// returns the map holding the heap
+    pub fn map(&self) -> &M {
+        &self.map
+    }
+
+    /// returns the map holding the heap, e.g. to grow it
+    pub fn map_mut(&mut self) -> &mut M {
+        &mut self.map
+    }
+
+    /// returns the map holding the heap, giving up the heap
+    pub fn into_inner(self) -> M {
+        self.map
+    }
+
+    fn alloc_bytes(&mut self, size: usize) -> Result<usize, Error> {
+        let exhausted = ErrorCode::HeapExhausted { size };
+        let class = size
+            .checked_add(BLOCK_HEADER)
+            .and_then(usize::checked_next_power_of_two)
+            .ok_or(exhausted.clone())?
+            .trailing_zeros()
+            .max(MIN_CLASS);
+        if class as usize >= MIN_CLASS as usize + CLASSES {
+            return Err(exhausted.into());
+        }
+        let header = self.header()?;
+        let free = &header.free[(class - MIN_CLASS) as usize];
+        let offset = match free.load(Ordering::Acquire) as usize {
+            0 => {
+                let block = header.top.load(Ordering::Relaxed) as usize;
+                let end = block
+                    .checked_add(1 << class)
+                    .filter(|end| *end <= self.map.as_ref().len())
+                    .ok_or(exhausted)?;
+                header.top.store(end as u64, Ordering::Release);
+                block + BLOCK_HEADER
+            }
+            offset => {
+                // The free list is stored in the object, where any process may have corrupted it.
+                let top = header.top.load(Ordering::Relaxed) as usize;
+                let block = self
+                    .block(offset, top)
+                    .filter(|block_class| *block_class == class)
+                    .map(|_| self.at::<BlockHeader>(offset - BLOCK_HEADER))
+                    .transpose()?
+                    .filter(|block| {
+                        block.tag.load(Ordering::Relaxed) == BLOCK_MAGIC | FREE | class as u64
+                    })
+                    .ok_or(ErrorCode::InvalidHeap)?;
+                free.store(block.next.load(Ordering::Relaxed), Ordering::Relaxed);
+                offset
+            }
+        };
+        let block = self.at::<BlockHeader>(offset - BLOCK_HEADER)?;
+        block.next.store(0, Ordering::Relaxed);
+        block
+            .tag
+            .store(BLOCK_MAGIC | class as u64, Ordering::Release);
+        Ok(offset)
+    }
+
+    ///
+    /// Returns the offset and size class of the block allocated for the given pointer, whose tag
+    /// holds the given state ([RETIRED] or none).
+    ///
+    fn allocated_block<T>(&self, ptr: ShmPtr<T>, state: u64) -> Result<(usize, u32), Error> {
+        let offset = ptr
+            .offset()
+            .ok_or(ErrorCode::InvalidAllocation { offset: 0 })?;
+        let top = self.header()?.top.load(Ordering::Relaxed) as usize;
+        let invalid = ErrorCode::InvalidAllocation { offset };
+        let class = self.block(offset, top).ok_or(invalid.clone())?;
+        let block = self.at::<BlockHeader>(offset - BLOCK_HEADER)?;
+        if block.tag.load(Ordering::Relaxed) != BLOCK_MAGIC | state | class as u64
+            || !self.starts_block(offset - BLOCK_HEADER, top)
+        {
+            return Err(invalid.into());
+        }
+        Ok((offset, class))
+    }
+
+    /// puts the block with the payload at the given offset on the free list of its class
+    fn release(&mut self, offset: usize, class: u32) -> Result<(), Error> {
+        let header = self.header()?;
+        let free = &header.free[(class - MIN_CLASS) as usize];
+        let block = self.at::<BlockHeader>(offset - BLOCK_HEADER)?;
+        block
+            .next
+            .store(free.load(Ordering::Relaxed), Ordering::Relaxed);
+        block
+            .tag
+            .store(BLOCK_MAGIC | FREE | class as u64, Ordering::Relaxed);
+        free.store(offset as u64, Ordering::Release);
+        Ok(())
+    }
+
+    fn record_mut(&mut self, offset: usize) -> Result<&mut Retired, Error> {
+        ShmPtr::<Retired>::new(offset)
+            .get_mut(&mut self.map)?
+            .ok_or_else(|| ErrorCode::InvalidHeap.into())
+    }
+
+    /// returns the size class of the block with the payload at the given offset, if it is in the
+    /// allocated memory and tagged as a block
+    fn block(&self, offset: usize, top: usize) -> Option<u32> {
+        if offset < FIRST_BLOCK + BLOCK_HEADER
+            || offset >= top
+            || !offset.is_multiple_of(BLOCK_ALIGN)
+        {
+            return None;
+        }
+        let block = offset - BLOCK_HEADER;
+        let tag = self
+            .at::<BlockHeader>(block)
+            .ok()?
+            .tag
+            .load(Ordering::Relaxed);
+        let class = (tag & CLASS_MASK) as u32;
+        (tag & !(FREE | RETIRED | CLASS_MASK) == BLOCK_MAGIC
+            && (MIN_CLASS..MIN_CLASS + CLASSES as u32).contains(&class)
+            && block + (1 << class) <= top)
+            .then_some(class)
+    }
+
+    /// returns true when a block starts at the given offset, walking the blocks from the first
+    /// one by their size: tags found within the payload of a block are never reached
+    fn starts_block(&self, block: usize, top: usize) -> bool {
+        let mut current = FIRST_BLOCK;
+        while current < block {
+            match self.block(current + BLOCK_HEADER, top) {
+                Some(class) => current += 1 << class,
+                None => return false,
+            }
+        }
+        current == block
+    }
+
+    fn header(&self) -> Result<&HeapHeader, Error> {
+        self.at::<HeapHeader>(0)
+    }
+
+    fn at<T: ShmSafe>(&self, offset: usize) -> Result<&T, Error> {
+        let memory = self.map.as_ref();
+        view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, 1)
+            .map(|ptr| unsafe { &*ptr })
+    }
+}
+
+impl<M: AsRef<[u8]>> AsRef<[u8]> for ShmHeap<M> {
+    fn as_ref(&self) -> &[u8] {
+        self.map.as_ref()
+    }
+}
+
+impl<M: AsMut<[u8]>> AsMut<[u8]> for ShmHeap<M> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.map.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::shm::{ErrorCode, ShmDefinition, ShmPtr};
+
+    use super::{ShmHeap, BLOCK_MAGIC, FIRST_BLOCK};
+
+    #[test]
+    fn alloc_returns_distinct_aligned_blocks() {
+        let definition = ShmDefinition::new("test_heap1", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+
+        let small = heap.alloc(1u8).unwrap();
+        let large = heap.alloc([2u64; 10]).unwrap();
+
+        assert_eq!(Some(FIRST_BLOCK + 16), small.offset());
+        assert_eq!(Some(FIRST_BLOCK + 32 + 16), large.offset());
+        assert_eq!(Some(&1), small.get(&heap).unwrap());
+        assert_eq!(Some(&[2u64; 10]), large.get(&heap).unwrap());
+    }
+
+    #[test]
+    fn free_blocks_are_reused_by_their_size_class() {
+        let definition = ShmDefinition::new("test_heap2", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let first = heap.alloc(1u64).unwrap();
+        let remaining = heap.remaining();
+
+        heap.free(first).unwrap();
+        let large = heap.alloc([0u64; 4]).unwrap();
+        let second = heap.alloc(2u64).unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first.offset(), large.offset());
+        assert_eq!(remaining - 64, heap.remaining());
+    }
+
+    #[test]
+    fn free_reports_an_error_for_invalid_pointers() {
+        let definition = ShmDefinition::new("test_heap3", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let value = heap.alloc(1u64).unwrap();
+        heap.free(value).unwrap();
+
+        assert_eq!(
+            ErrorCode::InvalidAllocation {
+                offset: value.offset().unwrap()
+            },
+            heap.free(value).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::InvalidAllocation { offset: 8 },
+            heap.free(ShmPtr::<u64>::new(8)).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn free_reports_an_error_for_pointers_within_a_block() {
+        let definition = ShmDefinition::new("test_heap7", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        // The payload looks like the size class of a block.
+        let value = heap.alloc([5u64; 10]).unwrap();
+        let inner = ShmPtr::<u64>::new(value.offset().unwrap() + 16);
+
+        assert_eq!(
+            ErrorCode::InvalidAllocation {
+                offset: inner.offset().unwrap()
+            },
+            heap.free(inner).unwrap_err()
+        );
+        heap.free(value).unwrap();
+    }
+
+    #[test]
+    fn free_reports_an_error_for_tags_forged_within_a_block() {
+        let definition = ShmDefinition::new("test_heap10", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        // The payload holds the tag of a live block of the smallest class, 16 bytes in.
+        let value = heap.alloc([0, 0, BLOCK_MAGIC | 5, 0, 0, 0]).unwrap();
+        let nested = ShmPtr::<u64>::new(value.offset().unwrap() + 32);
+
+        assert_eq!(
+            ErrorCode::InvalidAllocation {
+                offset: nested.offset().unwrap()
+            },
+            heap.free(nested).unwrap_err()
+        );
+        heap.free(value).unwrap();
+    }
+
+    #[test]
+    fn retired_blocks_are_reused_once_reclaimed() {
+        let definition = ShmDefinition::new("test_heap11", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let first = heap.alloc(1u64).unwrap();
+        heap.retire(first).unwrap();
+        let generation = heap.advance_generation().unwrap();
+        let second = heap.alloc(2u64).unwrap();
+        heap.retire(second).unwrap();
+
+        assert_ne!(first, heap.alloc(3u64).unwrap());
+        assert_eq!(Some(&1), first.get(&heap).unwrap());
+        assert_eq!(1, heap.reclaim(generation).unwrap());
+        assert_eq!(first, heap.alloc(4u64).unwrap());
+        assert_eq!(0, heap.reclaim(generation).unwrap());
+        let generation = heap.advance_generation().unwrap();
+        assert_eq!(1, heap.reclaim(generation).unwrap());
+        assert_eq!(second, heap.alloc(5u64).unwrap());
+    }
+
+    #[test]
+    fn retired_blocks_are_neither_freed_nor_retired_again() {
+        let definition = ShmDefinition::new("test_heap12", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let value = heap.alloc(1u64).unwrap();
+        heap.retire(value).unwrap();
+        let error = ErrorCode::InvalidAllocation {
+            offset: value.offset().unwrap(),
+        };
+
+        assert_eq!(error, heap.free(value).unwrap_err());
+        assert_eq!(error, heap.retire(value).unwrap_err());
+        let generation = heap.advance_generation().unwrap();
+        assert_eq!(1, heap.reclaim(generation).unwrap());
+        assert_eq!(error, heap.retire(value).unwrap_err());
+    }
+
+    #[test]
+    fn alloc_reports_an_error_when_the_free_list_is_corrupted() {
+        let definition = ShmDefinition::new("test_heap8", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let live = heap.alloc(1u64).unwrap();
+        let freed = heap.alloc(2u64).unwrap();
+        heap.free(freed).unwrap();
+        // The head of the free list of the smallest class follows the magic and the top.
+        let head = 16;
+
+        *heap.map_mut().view_mut::<u64>(head).unwrap() = 8;
+        assert_eq!(ErrorCode::InvalidHeap, heap.alloc(3u64).unwrap_err());
+        *heap.map_mut().view_mut::<u64>(head).unwrap() = live.offset().unwrap() as u64;
+        assert_eq!(ErrorCode::InvalidHeap, heap.alloc(3u64).unwrap_err());
+        *heap.map_mut().view_mut::<u64>(head).unwrap() = freed.offset().unwrap() as u64;
+        assert_eq!(freed, heap.alloc(3u64).unwrap());
+    }
+
+    #[test]
+    fn create_reports_an_error_when_the_memory_cannot_hold_the_metadata() {
+        let definition = ShmDefinition::new("test_heap9", NonZero::new(256).unwrap());
+
+        assert_eq!(
+            ErrorCode::HeapExhausted { size: FIRST_BLOCK },
+            ShmHeap::create(definition.create().unwrap()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn alloc_reports_an_error_when_the_heap_is_exhausted() {
+        let definition = ShmDefinition::new("test_heap4", NonZero::new(1024).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+
+        assert_eq!(
+            ErrorCode::HeapExhausted { size: 1024 },
+            heap.alloc([0u8; 1024]).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn open_takes_over_a_heap() {
+        let definition = ShmDefinition::new("test_heap5", NonZero::new(8192).unwrap());
+        let mut heap = ShmHeap::create(definition.create().unwrap()).unwrap();
+        let value = heap.alloc(1u64).unwrap();
+        let definition = ShmDefinition::new("test_heap5", NonZero::new(8192).unwrap());
+        let mut opened_heap = ShmHeap::open(definition.open().unwrap()).unwrap();
+
+        opened_heap.free(value).unwrap();
+
+        assert_eq!(value, heap.alloc(2u64).unwrap());
+        let definition = ShmDefinition::new("test_heap6", NonZero::new(8192).unwrap());
+        assert_eq!(
+            ErrorCode::InvalidHeap,
+            ShmHeap::open(definition.create().unwrap()).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use std::num::NonZero;
+use std::path::{Path, PathBuf};
+
+use nix::sys::memfd::MFdFlags;
+use nix::sys::mman::MapFlags;
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// The huge page sizes that can back a shared memory object.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HugePageSize {
+    /// 2 MiB pages (the default huge page size on x86_64).
+    TwoMiB,
+    /// 1 GiB pages (gigantic pages, usually reserved at boot time).
+    OneGiB,
+}
+
+impl HugePageSize {
+    /// The size of a page in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            HugePageSize::TwoMiB => 2 * 1024 * 1024,
+            HugePageSize::OneGiB => 1024 * 1024 * 1024,
+        }
+    }
+
+    ///
+    /// Rounds the given size up to a multiple of this page size.
+    ///
+    /// ```
+    /// use rshm::shm::HugePageSize;
+    ///
+    /// let size = std::num::NonZero::new(1024).unwrap();
+    /// assert_eq!(2 * 1024 * 1024, HugePageSize::TwoMiB.round_up(size).get());
+    /// ```
+    ///
+    pub fn round_up(&self, size: NonZero<usize>) -> NonZero<usize> {
+        size.get()
+            .checked_next_multiple_of(self.bytes())
+            .and_then(NonZero::new)
+            .unwrap_or(size)
+    }
+
+    pub(super) fn map_flags(&self) -> MapFlags {
+        match self {
+            HugePageSize::TwoMiB => MapFlags::MAP_HUGETLB | MapFlags::MAP_HUGE_2MB,
+            HugePageSize::OneGiB => MapFlags::MAP_HUGETLB | MapFlags::MAP_HUGE_1GB,
+        }
+    }
+
+    pub(super) fn memfd_flags(&self) -> MFdFlags {
+        match self {
+            HugePageSize::TwoMiB => MFdFlags::MFD_HUGETLB | MFdFlags::MFD_HUGE_2MB,
+            HugePageSize::OneGiB => MFdFlags::MFD_HUGETLB | MFdFlags::MFD_HUGE_1GB,
+        }
+    }
+
+    fn kilobytes(&self) -> usize {
+        self.bytes() / 1024
+    }
+}
+
+///
+/// Huge page settings of a shared memory definition.
+///
+#[derive(Debug, Clone)]
+pub(super) struct HugePages {
+    pub(super) size: HugePageSize,
+    /// The hugetlbfs mount point, discovered from /proc/mounts when not given.
+    pub(super) mount: Option<PathBuf>,
+}
+
+impl HugePages {
+    ///
+    /// The path of the file backing the shared memory object named `name`.
+    ///
+    pub(super) fn file_path(&self, name: &str) -> Result<PathBuf, Error> {
+        self.mount
+            .clone()
+            .or_else(|| find_hugetlbfs_mount(self.size))
+            .map(|mount| mount.join(name))
+            .ok_or(ErrorCode::HugeTlbFsNotMounted.into())
+    }
+
+    ///
+    /// Checks that the kernel supports this page size and has pages of that size reserved.
+    ///
+    pub(super) fn check_reserved(&self) -> Result<(), Error> {
+        let sysfs = format!(
+            "/sys/kernel/mm/hugepages/hugepages-{}kB/nr_hugepages",
+            self.size.kilobytes()
+        );
+        match std::fs::read_to_string(sysfs) {
+            Err(_) => Err(ErrorCode::HugePageSizeUnsupported.into()),
+            Ok(reserved) if reserved.trim() == "0" => Err(ErrorCode::NoHugePagesReserved.into()),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+///
+/// Looks up the first hugetlbfs mount point serving pages of the given size.
+///
+fn find_hugetlbfs_mount(size: HugePageSize) -> Option<PathBuf> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    let default_size = default_huge_page_size();
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?;
+            let fs_type = fields.next()?;
+            let options = fields.next()?;
+            (fs_type == "hugetlbfs").then_some((mount_point, options))
+        })
+        .find(|(_, options)| {
+            let page_size = options
+                .split(',')
+                .find_map(|option| option.strip_prefix("pagesize="))
+                .and_then(parse_size)
+                .or(default_size);
+            page_size == Some(size.bytes())
+        })
+        .map(|(mount_point, _)| Path::new(mount_point).to_path_buf())
+}
+
+///
+/// Reads the system's default huge page size from /proc/meminfo.
+///
+fn default_huge_page_size() -> Option<usize> {
+    std::fs::read_to_string("/proc/meminfo")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("Hugepagesize:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|kilobytes| kilobytes.trim().parse::<usize>().ok())
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+///
+/// Parses sizes as written in mount options (e.g. 2M, 1G, 2048k).
+///
+fn parse_size(value: &str) -> Option<usize> {
+    let (digits, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit())?);
+    let multiplier = match unit {
+        "k" | "K" => 1024,
+        "m" | "M" => 1024 * 1024,
+        "g" | "G" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok().map(|d| d * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::{parse_size, HugePageSize};
+
+    #[test]
+    fn round_up_aligns_sizes_on_the_page_size() {
+        let size = NonZero::new(3 * 1024 * 1024).expect("3MiB is not zero");
+
+        assert_eq!(4 * 1024 * 1024, HugePageSize::TwoMiB.round_up(size).get());
+        assert_eq!(
+            1024 * 1024 * 1024,
+            HugePageSize::OneGiB.round_up(size).get()
+        );
+    }
+
+    #[test]
+    fn round_up_keeps_sizes_already_aligned() {
+        let size = NonZero::new(4 * 1024 * 1024).expect("4MiB is not zero");
+
+        assert_eq!(size, HugePageSize::TwoMiB.round_up(size));
+    }
+
+    #[test]
+    fn parse_size_reads_mount_option_units() {
+        assert_eq!(Some(2 * 1024 * 1024), parse_size("2M"));
+        assert_eq!(Some(1024 * 1024 * 1024), parse_size("1G"));
+        assert_eq!(Some(2 * 1024 * 1024), parse_size("2048k"));
+        assert_eq!(None, parse_size("2048"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::num::NonZero;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use nix::errno::Errno;
+use nix::fcntl::{open, OFlag};
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::sys::stat::Mode;
+
+use super::header::Header;
+use super::{ErrorCode, HEADER_SIZE};
+use crate::Error;
+
+///
+/// The description of a shared memory object found by [list].
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentInfo {
+    /// The name of the object, as given to [ShmDefinition::new](super::ShmDefinition::new)
+    pub name: String,
+    /// The path of the object (e.g. /dev/shm/name)
+    pub path: PathBuf,
+    /// The size of the object, including its header if any
+    pub size: u64,
+    /// The permissions of the object (e.g. 0o600)
+    pub mode: u32,
+    /// The user owning the object
+    pub uid: u32,
+    /// The group owning the object
+    pub gid: u32,
+    /// The last modification time of the object (e.g. when it was truncated)
+    pub modified: SystemTime,
+    /// The rshm header of the object, when it has one and it is readable
+    pub header: Option<HeaderInfo>,
+}
+
+///
+/// The content of the rshm header of a shared memory object.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderInfo {
+    /// The version of the header format
+    pub version: u32,
+    /// The total size of the object recorded by its owner
+    pub size: u64,
+    /// The offset of the memory following the header
+    pub payload_offset: u64,
+    /// The fingerprint of the layout of the memory following the header, 0 when not given
+    pub fingerprint: u64,
+    /// The creation time of the object
+    pub created: SystemTime,
+    /// The number of times the object was grown
+    pub generation: u64,
+    /// Whether the owner marked the object as initialized
+    pub initialized: bool,
+    /// The process id of the owner, None when the object was given up by its owner
+    pub owner: Option<i32>,
+    /// Whether the owner died without unlinking the object
+    pub stale: bool,
+}
+
+///
+/// Lists the shared memory objects of /dev/shm, reading the header of those created by rshm.
+///
+/// ```
+/// use rshm::shm::ShmDefinition;
+///
+/// let definition = ShmDefinition::new("example_list", std::num::NonZero::new(8192).unwrap())
+///     .with_header();
+/// let _owned_shm = definition.create().unwrap();
+///
+/// let segments = rshm::shm::list().unwrap();
+/// let segment = segments.iter().find(|segment| segment.name == "example_list").unwrap();
+/// assert_eq!(8192, segment.size);
+/// assert!(!segment.header.as_ref().unwrap().stale);
+/// ```
+///
+pub fn list() -> Result<Vec<SegmentInfo>, Error> {
+    list_in("/dev/shm")
+}
+
+///
+/// Lists the shared memory objects of the given directory, such as a hugetlbfs mount
+/// (e.g. /dev/hugepages).
+///
+/// Objects removed while the directory is walked are skipped, and the header of objects that
+/// cannot be read by this process is not reported.
+///
+pub fn list_in(directory: impl AsRef<Path>) -> Result<Vec<SegmentInfo>, Error> {
+    let directory = directory.as_ref();
+    let entries = std::fs::read_dir(directory)
+        .map_err(|error| map_read_dir_error(error).with_path(&directory.to_string_lossy()))?;
+    let mut segments: Vec<SegmentInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata
+                .is_file()
+                .then(|| segment_info(entry.path(), &metadata))
+        })
+        .collect();
+    segments.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(segments)
+}
+
+fn segment_info(path: PathBuf, metadata: &Metadata) -> SegmentInfo {
+    SegmentInfo {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size: metadata.size(),
+        mode: metadata.mode() & 0o7777,
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        header: read_header(&path, metadata.size()),
+        path,
+    }
+}
+
+///
+/// Reads the header of the object at the given path, mapping the whole object
+/// (hugetlbfs objects cannot be read and are mapped by whole pages).
+///
+fn read_header(path: &Path, size: u64) -> Option<HeaderInfo> {
+    if size <= HEADER_SIZE as u64 {
+        return None;
+    }
+    let size = NonZero::new(usize::try_from(size).ok()?)?;
+    let fd = open(path, OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty()).ok()?;
+    let head = unsafe {
+        mmap(
+            None,
+            size,
+            ProtFlags::PROT_READ,
+            MapFlags::MAP_SHARED,
+            &fd,
+            0,
+        )
+    }
+    .ok()?;
+    let info = unsafe { Header::at(head.as_ptr() as *const u8) }.info();
+    let _unmap_result = unsafe { munmap(head, size.get()) };
+    info
+}
+
+fn map_read_dir_error(error: std::io::Error) -> Error {
+    let code = match error.kind() {
+        ErrorKind::NotFound => ErrorCode::ShmPathDoesNotExist,
+        ErrorKind::PermissionDenied => ErrorCode::ShmPathAccessDenied,
+        _ => error
+            .raw_os_error()
+            .map(|errno| ErrorCode::Unknown(Errno::from_raw(errno)))
+            .unwrap_or(ErrorCode::ShmPathInvalid),
+    };
+    match error.raw_os_error() {
+        Some(errno) => Error::from_errno(code, "opendir", Errno::from_raw(errno)),
+        None => code.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::shm::{ErrorCode, ShmDefinition, HEADER_SIZE, HEADER_VERSION};
+
+    use super::{list, list_in};
+
+    #[test]
+    fn list_reports_the_header_of_rshm_objects() {
+        let definition = ShmDefinition::new("test_list1", NonZero::new(8192).unwrap())
+            .with_header()
+            .with_mode(0o640)
+            .with_fingerprint(7);
+        let owned_shm = definition.create().unwrap();
+        owned_shm.mark_initialized().unwrap();
+        let definition = ShmDefinition::new("test_list2", NonZero::new(8192).unwrap());
+        let _owned_shm = definition.create().unwrap();
+
+        let segments = list().unwrap();
+        let with_header = segments
+            .iter()
+            .find(|segment| segment.name == "test_list1")
+            .unwrap();
+        let without_header = segments
+            .iter()
+            .find(|segment| segment.name == "test_list2")
+            .unwrap();
+
+        assert_eq!(8192, with_header.size);
+        assert_eq!(0o640, with_header.mode);
+        assert_eq!(nix::unistd::geteuid().as_raw(), with_header.uid);
+        let header = with_header.header.as_ref().unwrap();
+        assert_eq!(HEADER_VERSION, header.version);
+        assert_eq!(8192, header.size);
+        assert_eq!(HEADER_SIZE as u64, header.payload_offset);
+        assert_eq!(7, header.fingerprint);
+        assert_eq!(owned_shm.created(), Some(header.created));
+        assert!(header.initialized);
+        assert_eq!(Some(std::process::id() as i32), header.owner);
+        assert!(!header.stale);
+        assert_eq!(None, without_header.header);
+    }
+
+    #[test]
+    fn list_in_reports_an_error_when_the_directory_does_not_exist() {
+        assert_eq!(
+            ErrorCode::ShmPathDoesNotExist,
+            list_in("/dev/shm/test_list_missing").unwrap_err()
+        );
+    }
+}
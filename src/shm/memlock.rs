@@ -0,0 +1,100 @@
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use libc::c_void;
+use nix::errno::Errno;
+use nix::sys::mman::mlock;
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// Locks the given range in memory as its pages are faulted in (mlock2 with MLOCK_ONFAULT),
+/// or locks and faults in all its pages with mlock on kernels older than 4.4.
+///
+pub(super) fn lock(head: NonNull<c_void>, len: usize) -> Result<(), Error> {
+    match Errno::result(unsafe { libc::mlock2(head.as_ptr(), len, libc::MLOCK_ONFAULT) }) {
+        Ok(_) => Ok(()),
+        Err(Errno::ENOSYS) => {
+            unsafe { mlock(head, len) }.map_err(|errno| map_lock_error("mlock", errno))
+        }
+        Err(errno) => Err(map_lock_error("mlock2", errno)),
+    }
+}
+
+///
+/// Faults in the pages of the given range for writing, without modifying their content.
+///
+/// Each page is touched with an atomic addition of 0, which cannot lose a concurrent write.
+///
+pub(super) fn prefault_write(head: *const u8, len: usize, page_size: usize) {
+    for offset in (0..len).step_by(page_size) {
+        unsafe { AtomicU8::from_ptr(head.add(offset) as *mut u8) }.fetch_add(0, Ordering::Relaxed);
+    }
+}
+
+///
+/// Faults in the pages of the given range for reading.
+///
+pub(super) fn prefault_read(head: *const u8, len: usize, page_size: usize) {
+    for offset in (0..len).step_by(page_size) {
+        unsafe { head.add(offset).read_volatile() };
+    }
+}
+
+///
+/// The size of the base pages of the system.
+///
+pub(super) fn base_page_size() -> usize {
+    match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+        size if size > 0 => size as usize,
+        _ => 4096,
+    }
+}
+
+///
+/// The maximum number of bytes this process may lock in memory (RLIMIT_MEMLOCK),
+/// None when it is unlimited or unknown.
+///
+pub(super) fn memlock_limit() -> Option<u64> {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    match unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } {
+        0 if limit.rlim_cur != libc::RLIM_INFINITY => Some(limit.rlim_cur),
+        _ => None,
+    }
+}
+
+pub(super) fn map_lock_error(operation: &'static str, errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EAGAIN | Errno::ENOMEM | Errno::EPERM => ErrorCode::LockRefused {
+            memlock_limit: memlock_limit(),
+        },
+        other => ErrorCode::Unknown(other),
+    };
+    Error::from_errno(code, operation, errno)
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::errno::Errno;
+
+    use crate::shm::ErrorCode;
+
+    use super::{map_lock_error, memlock_limit};
+
+    #[test]
+    fn lock_errors_report_the_memlock_limit() {
+        let error = map_lock_error("mlock2", Errno::ENOMEM);
+
+        assert_eq!(
+            ErrorCode::LockRefused {
+                memlock_limit: memlock_limit()
+            },
+            error
+        );
+        assert_eq!(Some("mlock2"), error.operation());
+    }
+}
@@ -0,0 +1,190 @@
+use std::ptr::{self, NonNull};
+
+use libc::{c_int, c_ulong, c_void};
+use nix::errno::Errno;
+
+use super::memlock::base_page_size;
+use super::ErrorCode;
+use crate::Error;
+
+const MPOL_PREFERRED: c_int = 1;
+const MPOL_BIND: c_int = 2;
+const MPOL_INTERLEAVE: c_int = 3;
+
+///
+/// The NUMA nodes the memory of a shared memory object is allocated on (see mbind(2)).
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Allocates the memory on the given nodes only (MPOL_BIND).
+    Bind(Vec<u32>),
+    /// Allocates the memory on the given node when it has free memory (MPOL_PREFERRED).
+    Preferred(u32),
+    /// Allocates the pages in turn on each of the given nodes (MPOL_INTERLEAVE).
+    Interleave(Vec<u32>),
+}
+
+impl NumaPolicy {
+    fn mode(&self) -> c_int {
+        match self {
+            NumaPolicy::Bind(_) => MPOL_BIND,
+            NumaPolicy::Preferred(_) => MPOL_PREFERRED,
+            NumaPolicy::Interleave(_) => MPOL_INTERLEAVE,
+        }
+    }
+
+    fn nodes(&self) -> &[u32] {
+        match self {
+            NumaPolicy::Bind(nodes) | NumaPolicy::Interleave(nodes) => nodes,
+            NumaPolicy::Preferred(node) => std::slice::from_ref(node),
+        }
+    }
+
+    ///
+    /// The bit mask of the nodes of this policy, in words of the size expected by mbind.
+    ///
+    fn node_mask(&self) -> Vec<c_ulong> {
+        let bits = c_ulong::BITS as usize;
+        let highest = self.nodes().iter().max().map_or(0, |node| *node as usize);
+        let mut mask = vec![0; highest / bits + 1];
+        for node in self.nodes() {
+            mask[*node as usize / bits] |= 1 << (*node as usize % bits);
+        }
+        mask
+    }
+}
+
+///
+/// Applies the given policy to the mapping at `head`, before its pages are first touched.
+///
+/// On machines with a single node (or kernels without NUMA support), all the memory is
+/// allocated on that node whatever the policy: a policy naming other nodes is ignored.
+///
+pub(super) fn apply(policy: &NumaPolicy, head: NonNull<c_void>, len: usize) -> Result<(), Error> {
+    let mask = policy.node_mask();
+    // The kernel reads one bit less than the given maximum node.
+    let max_node = (mask.len() * c_ulong::BITS as usize + 1) as c_ulong;
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            head.as_ptr(),
+            len,
+            policy.mode(),
+            mask.as_ptr(),
+            max_node,
+            0,
+        )
+    };
+    match Errno::result(result) {
+        Ok(_) | Err(Errno::ENOSYS) => Ok(()),
+        Err(Errno::EINVAL) if online_nodes().len() <= 1 => Ok(()),
+        Err(Errno::EINVAL) => Err(Error::from_errno(
+            ErrorCode::NumaPolicyRefused(policy.clone()),
+            "mbind",
+            Errno::EINVAL,
+        )),
+        Err(other) => Err(Error::from_errno(ErrorCode::Unknown(other), "mbind", other)),
+    }
+}
+
+///
+/// The node of each page of the mapping at `head`, None for the pages that were not
+/// faulted in yet.
+///
+/// Without NUMA support in the kernel, the pages in memory are reported on node 0.
+///
+pub(super) fn page_nodes(
+    head: *const u8,
+    len: usize,
+    page_size: usize,
+) -> Result<Vec<Option<u32>>, Error> {
+    let pages: Vec<*const c_void> = (0..len)
+        .step_by(page_size)
+        .map(|offset| head.wrapping_add(offset) as *const c_void)
+        .collect();
+    let mut status: Vec<c_int> = vec![0; pages.len()];
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            0,
+            pages.len() as c_ulong,
+            pages.as_ptr(),
+            ptr::null::<c_int>(),
+            status.as_mut_ptr(),
+            0,
+        )
+    };
+    match Errno::result(result) {
+        Ok(_) => Ok(status
+            .iter()
+            .map(|node| u32::try_from(*node).ok())
+            .collect()),
+        Err(Errno::ENOSYS) => resident_pages(head, len, page_size),
+        Err(other) => Err(Error::from_errno(
+            ErrorCode::Unknown(other),
+            "move_pages",
+            other,
+        )),
+    }
+}
+
+///
+/// Reports the pages in memory on node 0, for kernels without NUMA support.
+///
+fn resident_pages(
+    head: *const u8,
+    len: usize,
+    page_size: usize,
+) -> Result<Vec<Option<u32>>, Error> {
+    let mut residency = vec![0u8; len.div_ceil(base_page_size())];
+    let result = unsafe { libc::mincore(head as *mut c_void, len, residency.as_mut_ptr()) };
+    Errno::result(result)
+        .map_err(|errno| Error::from_errno(ErrorCode::Unknown(errno), "mincore", errno))?;
+    let pages_per_page = page_size / base_page_size();
+    Ok(residency
+        .iter()
+        .step_by(pages_per_page.max(1))
+        .map(|resident| (resident & 1 == 1).then_some(0))
+        .collect())
+}
+
+///
+/// The nodes with memory that are online, empty when the kernel has no NUMA support.
+///
+fn online_nodes() -> Vec<u32> {
+    std::fs::read_to_string("/sys/devices/system/node/online")
+        .map(|nodes| parse_node_list(nodes.trim()))
+        .unwrap_or_default()
+}
+
+///
+/// Parses a list of nodes as found in /sys (e.g. 0-3,6).
+///
+fn parse_node_list(nodes: &str) -> Vec<u32> {
+    nodes
+        .split(',')
+        .filter_map(|range| match range.split_once('-') {
+            Some((first, last)) => Some(first.parse().ok()?..=last.parse().ok()?),
+            None => range.parse().ok().map(|node| node..=node),
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_node_list, NumaPolicy};
+
+    #[test]
+    fn parse_node_list_expands_ranges() {
+        assert_eq!(vec![0, 1, 2, 3, 6], parse_node_list("0-3,6"));
+        assert_eq!(vec![0], parse_node_list("0"));
+        assert!(parse_node_list("").is_empty());
+    }
+
+    #[test]
+    fn node_mask_sets_the_bit_of_each_node() {
+        assert_eq!(vec![0b101], NumaPolicy::Interleave(vec![0, 2]).node_mask());
+        assert_eq!(vec![0, 1 << 1], NumaPolicy::Preferred(65).node_mask());
+    }
+}
@@ -0,0 +1,81 @@
+use std::os::fd::{FromRawFd, OwnedFd};
+
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::Pid;
+
+///
+/// The id of this process, as recorded in headers.
+///
+pub(super) fn process_id() -> i32 {
+    std::process::id() as i32
+}
+
+///
+/// The start time of the given process, in clock ticks since boot (see proc_pid_stat(5)).
+///
+/// Together with the process id, it identifies a process even after its id is reused.
+///
+pub(super) fn process_start_time(pid: i32) -> Option<u64> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name (2nd field) is in parentheses and may contain spaces.
+    let (_, fields) = stat.rsplit_once(')')?;
+    // The start time is the 22nd field, fields following the command name start at the 3rd.
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+///
+/// Checks whether the process of the given id and start time (0 when unknown) is running.
+/// The start time is truncated to 32 bits, as recorded in headers.
+///
+/// The process is looked up with pidfd_open when the kernel supports it (5.3+), and with
+/// kill otherwise. A running process with a different start time reused the id of a dead one.
+///
+pub(super) fn is_alive(pid: i32, start_time: u32) -> bool {
+    let exists = match unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) } {
+        -1 => match Errno::last() {
+            Errno::ENOSYS => match kill(Pid::from_raw(pid), None) {
+                Ok(()) | Err(Errno::EPERM) => true,
+                Err(_) => false,
+            },
+            Errno::ESRCH => false,
+            _ => true,
+        },
+        pidfd => {
+            drop(unsafe { OwnedFd::from_raw_fd(pidfd as i32) });
+            true
+        }
+    };
+    exists
+        && (start_time == 0
+            || process_start_time(pid).is_none_or(|actual| actual as u32 == start_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_alive, process_id, process_start_time};
+
+    #[test]
+    fn is_alive_returns_true_for_this_process() {
+        let start_time = process_start_time(process_id()).unwrap() as u32;
+
+        assert!(is_alive(process_id(), start_time));
+        assert!(is_alive(process_id(), 0));
+    }
+
+    #[test]
+    fn is_alive_returns_false_when_the_process_id_was_reused() {
+        let start_time = process_start_time(process_id()).unwrap() as u32;
+
+        assert!(!is_alive(process_id(), start_time.wrapping_add(1)));
+    }
+
+    #[test]
+    fn is_alive_returns_false_for_a_dead_process() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id() as i32;
+        child.wait().unwrap();
+
+        assert!(!is_alive(pid, 0));
+    }
+}
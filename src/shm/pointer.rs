@@ -0,0 +1,345 @@
+use std::marker::PhantomData;
+
+use super::view::{self, ShmSafe};
+use crate::Error;
+
+///
+/// A pointer to a value of type T stored in a shared memory object, as an offset from the start
+/// of its memory (past its header, if any): it keeps its meaning in every process mapping the
+/// object, whatever the address of the mapping.
+///
+/// It is resolved against the memory of the mapped object (e.g. a [ShmMap](super::ShmMap)),
+/// and checked to fit in it. A zeroed pointer is null, so that the pointers of a new object are.
+///
+/// ```
+/// use rshm::shm::{ShmDefinition, ShmPtr, ShmSafe};
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(C)]
+/// struct Node {
+///     value: u64,
+///     next: ShmPtr<Node>,
+/// }
+/// unsafe impl ShmSafe for Node {}
+///
+/// let definition = ShmDefinition::new("example_ptr", std::num::NonZero::new(1024).unwrap());
+/// let mut shm = definition.create().unwrap();
+/// *shm.view_mut::<Node>(0).unwrap() = Node { value: 1, next: ShmPtr::new(16) };
+/// *shm.view_mut::<Node>(16).unwrap() = Node { value: 2, next: ShmPtr::null() };
+///
+/// let mut values = Vec::new();
+/// let mut node = ShmPtr::<Node>::new(0);
+/// while let Some(current) = node.get(&shm).unwrap() {
+///     values.push(current.value);
+///     node = current.next;
+/// }
+/// assert_eq!(vec![1, 2], values);
+/// ```
+///
+#[repr(transparent)]
+pub struct ShmPtr<T> {
+    /// The offset of the value plus one, 0 for a null pointer.
+    offset: u64,
+    target: PhantomData<fn() -> T>,
+}
+
+// Any offset is valid: it is checked against the memory of the object when resolved.
+unsafe impl<T> ShmSafe for ShmPtr<T> {}
+
+impl<T> Clone for ShmPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ShmPtr<T> {}
+
+impl<T> PartialEq for ShmPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> Eq for ShmPtr<T> {}
+
+impl<T> std::fmt::Debug for ShmPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ShmPtr").field(&self.offset()).finish()
+    }
+}
+
+impl<T> Default for ShmPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> ShmPtr<T> {
+    /// returns a pointer to nothing
+    pub const fn null() -> Self {
+        ShmPtr {
+            offset: 0,
+            target: PhantomData,
+        }
+    }
+
+    ///
+    /// Returns a pointer to the value at the given offset from the start of the memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the offset is `usize::MAX`, which no value of a memory can start at:
+    /// the pointer stores the offset plus one to keep zero for null.
+    ///
+    pub const fn new(offset: usize) -> Self {
+        assert!(
+            offset < usize::MAX,
+            "the offset of a ShmPtr must be below usize::MAX"
+        );
+        ShmPtr {
+            offset: offset as u64 + 1,
+            target: PhantomData,
+        }
+    }
+
+    /// returns true when the pointer points to nothing
+    pub fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// returns the offset of the value from the start of the memory, None for a null pointer
+    pub fn offset(&self) -> Option<usize> {
+        self.offset.checked_sub(1).map(|offset| offset as usize)
+    }
+
+    /// returns the pointer represented by the given bits, e.g. loaded from an atomic
+    pub(super) const fn from_bits(bits: u64) -> Self {
+        ShmPtr {
+            offset: bits,
+            target: PhantomData,
+        }
+    }
+
+    /// returns the bits representing the pointer, e.g. to store it in an atomic
+    pub(super) const fn to_bits(self) -> u64 {
+        self.offset
+    }
+}
+
+impl<T: ShmSafe> ShmPtr<T> {
+    ///
+    /// Returns a pointer to the given value, which must lie in the given memory.
+    ///
+    pub fn to<M: AsRef<[u8]> + ?Sized>(memory: &M, value: &T) -> Result<Self, Error> {
+        let memory = memory.as_ref();
+        let offset = (value as *const T as usize).wrapping_sub(memory.as_ptr() as usize);
+        view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, 1)?;
+        Ok(Self::new(offset))
+    }
+
+    ///
+    /// Resolves the pointer against the given memory, returning None for a null pointer and an
+    /// error when the value does not fit in the memory or is not aligned.
+    ///
+    pub fn get<'a, M: AsRef<[u8]> + ?Sized>(&self, memory: &'a M) -> Result<Option<&'a T>, Error> {
+        let memory = memory.as_ref();
+        self.offset()
+            .map(|offset| {
+                view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, 1)
+                    .map(|ptr| unsafe { &*ptr })
+            })
+            .transpose()
+    }
+
+    ///
+    /// Mutably resolves the pointer against the given memory (see [get](Self::get)).
+    ///
+    pub fn get_mut<'a, M: AsMut<[u8]> + ?Sized>(
+        &self,
+        memory: &'a mut M,
+    ) -> Result<Option<&'a mut T>, Error> {
+        let memory = memory.as_mut();
+        self.offset()
+            .map(|offset| {
+                view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, 1)
+                    .map(|ptr| unsafe { &mut *(ptr as *mut T) })
+            })
+            .transpose()
+    }
+}
+
+///
+/// A pointer to a value of type T stored in a shared memory object, as an offset from the
+/// pointer itself: the structures holding it can be moved or copied as a whole within the
+/// memory, or to another object, and keep their inner references.
+///
+/// It is only meaningful in place, and is neither Copy nor Clone. As a [ShmPtr], it is resolved
+/// against the memory of the mapped object and checked to fit in it. A zeroed pointer is null.
+///
+/// ```
+/// use rshm::shm::{RelPtr, ShmDefinition};
+///
+/// let definition = ShmDefinition::new("example_rel_ptr", std::num::NonZero::new(1024).unwrap());
+/// let mut shm = definition.create().unwrap();
+/// *shm.view_mut::<u64>(64).unwrap() = 42;
+/// let target = unsafe { shm.head().add(64) } as *const u64;
+/// shm.view_mut::<RelPtr<u64>>(0).unwrap().set(target);
+///
+/// let pointer = shm.view::<RelPtr<u64>>(0).unwrap();
+/// assert_eq!(Some(&42), pointer.get(&shm).unwrap());
+/// ```
+///
+#[repr(transparent)]
+pub struct RelPtr<T> {
+    /// The distance from this pointer to the value, 0 for a null pointer.
+    delta: i64,
+    target: PhantomData<fn() -> T>,
+}
+
+// Any distance is valid: it is checked against the memory of the object when resolved.
+unsafe impl<T> ShmSafe for RelPtr<T> {}
+
+impl<T> std::fmt::Debug for RelPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("RelPtr").field(&self.delta).finish()
+    }
+}
+
+impl<T> Default for RelPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> RelPtr<T> {
+    /// returns a pointer to nothing
+    pub const fn null() -> Self {
+        RelPtr {
+            delta: 0,
+            target: PhantomData,
+        }
+    }
+
+    /// returns true when the pointer points to nothing
+    pub fn is_null(&self) -> bool {
+        self.delta == 0
+    }
+
+    ///
+    /// Points to the value at the given address, which should lie in the memory holding this
+    /// pointer (it is checked when the pointer is resolved). A null address makes it null.
+    ///
+    pub fn set(&mut self, target: *const T) {
+        self.delta = if target.is_null() {
+            0
+        } else {
+            (target as usize).wrapping_sub(self as *const Self as usize) as i64
+        };
+    }
+}
+
+impl<T: ShmSafe> RelPtr<T> {
+    ///
+    /// Converts this pointer, which must lie in the given memory, to a pointer relative to the
+    /// start of the memory, e.g. to resolve it mutably with [ShmPtr::get_mut].
+    ///
+    pub fn resolve<M: AsRef<[u8]> + ?Sized>(&self, memory: &M) -> Result<ShmPtr<T>, Error> {
+        if self.is_null() {
+            return Ok(ShmPtr::null());
+        }
+        let memory = memory.as_ref();
+        let base = memory.as_ptr() as usize;
+        let position = (self as *const Self as usize).wrapping_sub(base);
+        view::checked_array::<Self>(memory.as_ptr(), memory.len(), position, 1)?;
+        let offset = position.wrapping_add(self.delta as usize);
+        view::checked_array::<T>(memory.as_ptr(), memory.len(), offset, 1)?;
+        Ok(ShmPtr::new(offset))
+    }
+
+    ///
+    /// Resolves the pointer against the given memory, returning None for a null pointer and an
+    /// error when the pointer or the value does not fit in the memory.
+    ///
+    pub fn get<'a, M: AsRef<[u8]> + ?Sized>(&self, memory: &'a M) -> Result<Option<&'a T>, Error> {
+        self.resolve(memory)?.get(memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::shm::{ErrorCode, ShmDefinition};
+
+    use super::{RelPtr, ShmPtr};
+
+    #[test]
+    fn shm_ptr_resolves_the_value_at_its_offset() {
+        let definition = ShmDefinition::new("test_ptr1", NonZero::new(32).unwrap());
+        let mut owned_shm = definition.create().unwrap();
+
+        *ShmPtr::<u64>::new(8)
+            .get_mut(&mut owned_shm)
+            .unwrap()
+            .unwrap() = 7;
+
+        assert_eq!(7, *owned_shm.view::<u64>(8).unwrap());
+        let value = owned_shm.view::<u64>(8).unwrap();
+        assert_eq!(Some(8), ShmPtr::to(&owned_shm, value).unwrap().offset());
+    }
+
+    #[test]
+    fn shm_ptr_is_null_when_zeroed() {
+        let definition = ShmDefinition::new("test_ptr2", NonZero::new(32).unwrap());
+        let owned_shm = definition.create().unwrap();
+
+        let pointer = owned_shm.view::<ShmPtr<u64>>(0).unwrap();
+
+        assert!(pointer.is_null());
+        assert_eq!(None, pointer.get(&owned_shm).unwrap());
+    }
+
+    #[test]
+    fn shm_ptr_reports_an_error_when_out_of_bounds() {
+        let definition = ShmDefinition::new("test_ptr3", NonZero::new(32).unwrap());
+        let owned_shm = definition.create().unwrap();
+
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds { end: 40, len: 32 },
+            ShmPtr::<u64>::new(32).get(&owned_shm).unwrap_err()
+        );
+        let value = owned_shm.view::<u64>(0).unwrap();
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: usize::MAX,
+                len: 24
+            },
+            ShmPtr::to(&owned_shm.as_slice()[8..], value).unwrap_err()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the offset of a ShmPtr must be below usize::MAX")]
+    fn shm_ptr_refuses_the_largest_offset() {
+        ShmPtr::<u8>::new(usize::MAX);
+    }
+
+    #[test]
+    fn rel_ptr_resolves_against_its_own_position() {
+        let definition = ShmDefinition::new("test_ptr4", NonZero::new(32).unwrap());
+        let mut owned_shm = definition.create().unwrap();
+        *owned_shm.view_mut::<u64>(24).unwrap() = 7;
+        let target = owned_shm.view::<u64>(24).unwrap() as *const u64;
+
+        owned_shm.view_mut::<RelPtr<u64>>(8).unwrap().set(target);
+
+        let pointer = owned_shm.view::<RelPtr<u64>>(8).unwrap();
+        assert_eq!(16, *owned_shm.view::<i64>(8).unwrap());
+        assert_eq!(Some(24), pointer.resolve(&owned_shm).unwrap().offset());
+        assert_eq!(Some(&7), pointer.get(&owned_shm).unwrap());
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds { end: 32, len: 16 },
+            pointer.get(&owned_shm.as_slice()[..16]).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::ops::Range;
+use std::ptr::NonNull;
+
+use libc::c_void;
+use nix::errno::Errno;
+use nix::sys::mman::{mprotect, ProtFlags};
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// The accesses allowed to a range of a mapped shared memory object (see mprotect(2)).
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protection {
+    /// Any access faults (PROT_NONE).
+    None,
+    /// The range can be read, writing faults (PROT_READ).
+    Read,
+    /// The range can be read and written (PROT_READ | PROT_WRITE), when the object was mapped
+    /// for writing.
+    ReadWrite,
+}
+
+impl Protection {
+    fn to_prot_flags(self) -> ProtFlags {
+        match self {
+            Protection::None => ProtFlags::PROT_NONE,
+            Protection::Read => ProtFlags::PROT_READ,
+            Protection::ReadWrite => ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+        }
+    }
+}
+
+///
+/// Changes the protection of the given range of the `len` bytes mapped at `head`. The range
+/// must start on a page and end on a page or at the end of the mapping.
+///
+pub(super) fn protect(
+    head: *const u8,
+    len: usize,
+    range: Range<usize>,
+    protection: Protection,
+    page_size: usize,
+) -> Result<(), Error> {
+    if range.start > range.end || range.end > len {
+        return Err(ErrorCode::ViewOutOfBounds {
+            end: range.end,
+            len,
+        }
+        .into());
+    }
+    if let Some(offset) = [range.start, range.end]
+        .into_iter()
+        .find(|offset| !(head as usize + offset).is_multiple_of(page_size) && *offset != len)
+    {
+        return Err(ErrorCode::ProtectionMisaligned { offset, page_size }.into());
+    }
+    let Some(start) =
+        NonNull::new(head.wrapping_add(range.start) as *mut c_void).filter(|_| !range.is_empty())
+    else {
+        return Ok(());
+    };
+    unsafe { mprotect(start, range.len(), protection.to_prot_flags()) }.map_err(|errno| {
+        let code = match errno {
+            Errno::EACCES => ErrorCode::ProtectionRefused(protection),
+            other => ErrorCode::Unknown(other),
+        };
+        Error::from_errno(code, "mprotect", errno)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shm::ErrorCode;
+
+    use super::{protect, Protection};
+
+    #[test]
+    fn protect_reports_an_error_for_ranges_not_on_pages() {
+        let head = 4096 as *const u8;
+
+        assert_eq!(
+            ErrorCode::ProtectionMisaligned {
+                offset: 100,
+                page_size: 4096
+            },
+            protect(head, 8192, 100..4096, Protection::Read, 4096).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::ProtectionMisaligned {
+                offset: 5000,
+                page_size: 4096
+            },
+            protect(head, 8192, 0..5000, Protection::Read, 4096).unwrap_err()
+        );
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: 12288,
+                len: 8192
+            },
+            protect(head, 8192, 4096..12288, Protection::Read, 4096).unwrap_err()
+        );
+    }
+}
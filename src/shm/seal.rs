@@ -0,0 +1,103 @@
+use std::num::NonZero;
+use std::os::fd::AsFd;
+use std::ptr::NonNull;
+
+use libc::c_void;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, SealFlag};
+use nix::sys::mman::{mmap_anonymous, MapFlags, ProtFlags};
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// Adds the given seals to the memfd object referred to by the file descriptor.
+///
+pub(super) fn add_seals<Fd: AsFd>(fd: &Fd, seals: SealFlag) -> Result<(), Error> {
+    fcntl(fd, FcntlArg::F_ADD_SEALS(seals))
+        .map(|_| ())
+        .map_err(|errno| {
+            let code = match errno {
+                // EPERM: the object was not created with MFD_ALLOW_SEALING, or is sealed with
+                // F_SEAL_SEAL. EBUSY: a writable mapping of the object prevents F_SEAL_WRITE.
+                Errno::EPERM | Errno::EBUSY | Errno::EINVAL => ErrorCode::SealingRefused,
+                other => ErrorCode::Unknown(other),
+            };
+            Error::from_errno(code, "fcntl", errno)
+        })
+}
+
+///
+/// The seals of the object referred to by the file descriptor (empty when it cannot be sealed).
+///
+pub(super) fn seals<Fd: AsFd>(fd: &Fd) -> Result<SealFlag, Error> {
+    match fcntl(fd, FcntlArg::F_GET_SEALS) {
+        Ok(seals) => Ok(SealFlag::from_bits_truncate(seals)),
+        Err(Errno::EINVAL) => Ok(SealFlag::empty()),
+        Err(errno) => Err(Error::from_errno(ErrorCode::Unknown(errno), "fcntl", errno)),
+    }
+}
+
+///
+/// Checks that the object referred to by the file descriptor carries the required seals.
+///
+pub(super) fn check_seals<Fd: AsFd>(fd: &Fd, required: SealFlag) -> Result<(), Error> {
+    let actual = seals(fd)?;
+    if actual.contains(required) {
+        Ok(())
+    } else {
+        Err(ErrorCode::SealsMissing { required, actual }.into())
+    }
+}
+
+///
+/// Replaces the mapping of `size` bytes at `head` by reserved PROT_NONE pages, so that its
+/// address is kept for a mapping placed there again with MAP_FIXED.
+///
+pub(super) fn vacate(head: NonNull<c_void>, size: usize) -> Result<(), Errno> {
+    unsafe {
+        mmap_anonymous(
+            NonZero::new(head.as_ptr() as usize),
+            NonZero::new(size).ok_or(Errno::EINVAL)?,
+            ProtFlags::PROT_NONE,
+            MapFlags::MAP_PRIVATE | MapFlags::MAP_NORESERVE | MapFlags::MAP_FIXED,
+        )
+    }
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::fcntl::SealFlag;
+    use nix::sys::memfd::{memfd_create, MFdFlags};
+
+    use crate::shm::ErrorCode;
+
+    use super::{add_seals, check_seals, seals};
+
+    #[test]
+    fn check_seals_reports_the_missing_seals() {
+        let fd = memfd_create("test_seal", MFdFlags::MFD_ALLOW_SEALING).unwrap();
+        add_seals(&fd, SealFlag::F_SEAL_GROW).unwrap();
+
+        assert_eq!(SealFlag::F_SEAL_GROW, seals(&fd).unwrap());
+        assert!(check_seals(&fd, SealFlag::F_SEAL_GROW).is_ok());
+        assert_eq!(
+            ErrorCode::SealsMissing {
+                required: SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_WRITE,
+                actual: SealFlag::F_SEAL_GROW,
+            },
+            check_seals(&fd, SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_WRITE).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn add_seals_is_refused_without_allow_sealing() {
+        let fd = memfd_create("test_seal", MFdFlags::empty()).unwrap();
+
+        assert_eq!(
+            ErrorCode::SealingRefused,
+            add_seals(&fd, SealFlag::F_SEAL_GROW).unwrap_err()
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::RwLock;
+
+use super::ShmDefinition;
+use crate::Error;
+
+/// A function called with the definition of a dropped object and the error that occurred.
+pub type DropErrorHook = fn(&ShmDefinition, &Error);
+
+/// The hook called with the errors that occur when a mapped object is dropped.
+static DROP_ERROR_HOOK: RwLock<Option<DropErrorHook>> = RwLock::new(None);
+
+///
+/// Sets the hook called with the errors that occur when a mapped shared memory object is dropped
+/// (e.g. when the object was already unlinked by an operator cleaning /dev/shm).
+///
+/// Drop never panics: without a hook these errors are ignored. Use `close` on the mapped
+/// object to handle them where they occur.
+///
+/// ```
+/// use rshm::shm::{set_drop_error_hook, ShmDefinition};
+/// use rshm::Error;
+///
+/// fn log_drop_error(definition: &ShmDefinition, error: &Error) {
+///     eprintln!("failed to release {}: {:?}", definition.path, error);
+/// }
+///
+/// set_drop_error_hook(Some(log_drop_error));
+/// ```
+///
+pub fn set_drop_error_hook(hook: Option<DropErrorHook>) {
+    *DROP_ERROR_HOOK
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = hook;
+}
+
+///
+/// Reports an error that occurred when dropping a mapped object to the hook, if any.
+///
+pub(super) fn report_drop_error(definition: &ShmDefinition, error: &Error) {
+    let hook = *DROP_ERROR_HOOK
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(hook) = hook {
+        hook(definition, error);
+    }
+}
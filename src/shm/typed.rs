@@ -0,0 +1,166 @@
+use std::marker::PhantomData;
+use std::mem::{align_of, needs_drop, size_of};
+use std::num::NonZero;
+use std::ops::Deref;
+use std::time::Duration;
+
+use crate::Error;
+
+use super::{OwnedShmMap, ShmDefinition, ShmMap, ShmSafe, HEADER_SIZE};
+
+///
+/// A value of type T placed in a shared memory object, following its header.
+///
+/// The object is sized for T, and its header records the layout of T, so that processes opening
+/// it with another type get a [LayoutMismatch](super::ErrorCode::LayoutMismatch) error. The value is
+/// shared through `&T`: it is modified with interior mutability (e.g. atomics).
+///
+/// ```
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use rshm::shm::Shm;
+///
+/// let owned_counter = Shm::create("example_typed", AtomicU64::new(1)).unwrap();
+/// let counter = Shm::<AtomicU64>::open("example_typed").unwrap();
+///
+/// counter.fetch_add(1, Ordering::AcqRel);
+/// assert_eq!(2, owned_counter.load(Ordering::Acquire));
+/// ```
+///
+/// Types that need to be dropped are rejected when the program is built:
+///
+/// ```compile_fail
+/// use rshm::shm::{Shm, ShmSafe};
+///
+/// struct Guard(u64);
+/// unsafe impl ShmSafe for Guard {}
+/// impl Drop for Guard {
+///     fn drop(&mut self) {}
+/// }
+///
+/// let guard = Shm::create("example_typed_drop", Guard(1));
+/// ```
+///
+#[derive(Debug)]
+pub struct Shm<T: ShmSafe> {
+    map: Mapping,
+    _value: PhantomData<T>,
+}
+
+/// The mapping holding the value, which is unlinked when dropped if it was created by this process.
+#[derive(Debug)]
+enum Mapping {
+    Owned(OwnedShmMap),
+    Opened(ShmMap),
+}
+
+impl<T: ShmSafe> Shm<T> {
+    ///
+    /// Creates the shared memory object of the given name and moves the initial value in it.
+    /// The object is marked as initialized once the value is written.
+    ///
+    pub fn create(path: impl Into<String>, init: T) -> Result<Shm<T>, Error> {
+        let map = Self::definition(path).create()?;
+        unsafe { (map.head() as *mut T).write(init) };
+        map.mark_initialized()?;
+        Ok(Shm {
+            map: Mapping::Owned(map),
+            _value: PhantomData,
+        })
+    }
+
+    ///
+    /// Opens the shared memory object of the given name, which must hold a value of type T.
+    ///
+    pub fn open(path: impl Into<String>) -> Result<Shm<T>, Error> {
+        Self::definition(path).open().map(|map| Shm {
+            map: Mapping::Opened(map),
+            _value: PhantomData,
+        })
+    }
+
+    ///
+    /// Opens the shared memory object of the given name, waiting for its owner to create it
+    /// (see [open_wait](ShmDefinition::open_wait)).
+    ///
+    pub fn open_wait(path: impl Into<String>, timeout: Duration) -> Result<Shm<T>, Error> {
+        Self::definition(path).open_wait(timeout).map(|map| Shm {
+            map: Mapping::Opened(map),
+            _value: PhantomData,
+        })
+    }
+
+    fn definition(path: impl Into<String>) -> ShmDefinition {
+        const {
+            assert!(
+                !needs_drop::<T>(),
+                "values in shared memory are never dropped"
+            );
+            assert!(
+                align_of::<T>() <= HEADER_SIZE,
+                "values in shared memory are aligned on the end of the header"
+            );
+        };
+        let size =
+            NonZero::new(HEADER_SIZE + size_of::<T>().max(1)).expect("the header size is not zero");
+        ShmDefinition::new(path, size)
+            .with_header()
+            .with_layout::<T>()
+    }
+}
+
+impl<T: ShmSafe> Deref for Shm<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        let head = match &self.map {
+            Mapping::Owned(map) => map.head(),
+            Mapping::Opened(map) => map.head(),
+        };
+        unsafe { &*(head as *const T) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use crate::condvar::Condvar;
+    use crate::shm::ErrorCode;
+
+    use super::Shm;
+
+    #[test]
+    fn open_shares_the_value_of_the_owner() {
+        let owned_counters =
+            Shm::create("test_typed1", [AtomicU64::new(1), AtomicU64::new(2)]).unwrap();
+        let counters = Shm::<[AtomicU64; 2]>::open("test_typed1").unwrap();
+
+        counters[1].store(3, Ordering::Release);
+
+        assert_eq!(1, owned_counters[0].load(Ordering::Acquire));
+        assert_eq!(3, owned_counters[1].load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn open_reports_an_error_when_the_type_differs() {
+        let _owned_counter = Shm::create("test_typed2", AtomicU64::new(1)).unwrap();
+
+        let error = Shm::<AtomicI64>::open("test_typed2").unwrap_err();
+
+        assert!(matches!(
+            error.code(),
+            Some(ErrorCode::LayoutMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn open_wait_returns_once_the_owner_created_the_value() {
+        let waiter = std::thread::spawn(|| {
+            Shm::<Condvar>::open_wait("test_typed3", Duration::from_secs(5)).map(|_| ())
+        });
+        let _owned_condvar = Shm::create("test_typed3", Condvar::new()).unwrap();
+
+        waiter.join().unwrap().unwrap();
+    }
+}
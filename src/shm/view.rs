@@ -0,0 +1,126 @@
+use std::mem::{align_of, size_of};
+use std::sync::atomic::{
+    AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize, AtomicU16, AtomicU32, AtomicU64,
+    AtomicU8, AtomicUsize,
+};
+
+use super::ErrorCode;
+use crate::Error;
+
+///
+/// Marks the types that can be viewed in a shared memory object.
+///
+/// # Safety
+/// Any bit pattern (including all zeroes, the content of a new object) must be a valid value of
+/// the type, and the value must keep its meaning in other processes: the type cannot hold
+/// pointers or references. Values are shared through memory and never dropped.
+///
+/// Memory written concurrently by other processes should be viewed as atomics (e.g. [AtomicU64]).
+///
+pub unsafe trait ShmSafe: Sized {}
+
+macro_rules! shm_safe {
+    ($($t:ty),*) => {
+        $(unsafe impl ShmSafe for $t {})*
+    };
+}
+
+shm_safe!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+shm_safe!(
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    AtomicU64,
+    AtomicUsize,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    AtomicI64,
+    AtomicIsize
+);
+
+unsafe impl<T: ShmSafe, const N: usize> ShmSafe for [T; N] {}
+
+///
+/// Checks that `count` values of type T fit in the `len` bytes from `head` at the given offset,
+/// and that they are aligned, returning a pointer to the first one.
+///
+pub(super) fn checked_array<T: ShmSafe>(
+    head: *const u8,
+    len: usize,
+    offset: usize,
+    count: usize,
+) -> Result<*const T, Error> {
+    let end = size_of::<T>()
+        .checked_mul(count)
+        .and_then(|size| size.checked_add(offset))
+        .unwrap_or(usize::MAX);
+    if end > len {
+        return Err(ErrorCode::ViewOutOfBounds { end, len }.into());
+    }
+    let ptr = head.wrapping_add(offset) as *const T;
+    if !ptr.is_aligned() {
+        return Err(ErrorCode::ViewMisaligned {
+            offset,
+            align: align_of::<T>(),
+        }
+        .into());
+    }
+    Ok(ptr)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shm::ErrorCode;
+
+    use super::checked_array;
+
+    #[test]
+    fn checked_array_rejects_values_past_the_end() {
+        let memory = [0u64; 4];
+
+        let error = checked_array::<u64>(memory.as_ptr() as *const u8, 32, 8, 4).unwrap_err();
+
+        assert_eq!(ErrorCode::ViewOutOfBounds { end: 40, len: 32 }, error);
+    }
+
+    #[test]
+    fn checked_array_rejects_overflowing_counts() {
+        let memory = [0u64; 4];
+
+        let error =
+            checked_array::<u64>(memory.as_ptr() as *const u8, 32, 0, usize::MAX).unwrap_err();
+
+        assert_eq!(
+            ErrorCode::ViewOutOfBounds {
+                end: usize::MAX,
+                len: 32
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn checked_array_rejects_misaligned_values() {
+        let memory = [0u64; 4];
+
+        let error = checked_array::<u64>(memory.as_ptr() as *const u8, 32, 4, 1).unwrap_err();
+
+        assert_eq!(
+            ErrorCode::ViewMisaligned {
+                offset: 4,
+                align: 8
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn checked_array_returns_a_pointer_to_the_first_value() {
+        let memory = [0u64, 1, 2, 3];
+
+        let ptr = checked_array::<u64>(memory.as_ptr() as *const u8, 32, 8, 3).unwrap();
+
+        assert_eq!(1, unsafe { ptr.read() });
+    }
+}
@@ -0,0 +1,97 @@
+use std::os::fd::{AsFd, OwnedFd};
+use std::time::Instant;
+
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use nix::sys::stat::Mode;
+
+use crate::Error;
+
+use super::{object_size, ErrorCode, ShmDefinition};
+
+///
+/// Opens the shared memory object of the given definition once it exists and was sized by its owner.
+///
+/// The directory holding the object is watched with inotify, so that the object is open as soon as
+/// it is created (IN_CREATE, IN_MOVED_TO) and checked again when it is truncated (IN_MODIFY).
+///
+pub(super) fn wait_for_fd(
+    definition: &ShmDefinition,
+    flags: OFlag,
+    deadline: Instant,
+) -> Result<OwnedFd, Error> {
+    let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+        .map_err(|errno| map_inotify_error("inotify_init1", errno))
+        .map_err(|error| definition.error(error))?;
+    inotify
+        .add_watch(
+            &definition.directory()?,
+            AddWatchFlags::IN_CREATE | AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_MODIFY,
+        )
+        .map_err(map_watch_error)
+        .map_err(|error| definition.error(error))?;
+    let mut opened = None;
+    loop {
+        if opened.is_none() {
+            opened = match definition.open_named(flags, Mode::empty()) {
+                Ok(fd) => Some(fd),
+                Err(error) if error == ErrorCode::ShmPathDoesNotExist => None,
+                Err(other) => return Err(other),
+            };
+        }
+        if let Some(fd) = opened.take() {
+            if object_size(&fd).map_err(|error| definition.error(error))? > 0 {
+                return Ok(fd);
+            }
+            opened = Some(fd);
+        }
+        wait_for_events(&inotify, deadline).map_err(|error| definition.error(error))?;
+    }
+}
+
+fn wait_for_events(inotify: &Inotify, deadline: Instant) -> Result<(), Error> {
+    let remaining = deadline
+        .checked_duration_since(Instant::now())
+        .ok_or(ErrorCode::WaitTimedOut)?;
+    let mut fds = [PollFd::new(inotify.as_fd(), PollFlags::POLLIN)];
+    match poll(
+        &mut fds,
+        PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX),
+    ) {
+        Ok(0) | Err(Errno::EINTR) => Ok(()),
+        Ok(_) => match inotify.read_events() {
+            Ok(_) | Err(Errno::EAGAIN) => Ok(()),
+            Err(other) => Err(map_inotify_error("read", other)),
+        },
+        Err(other) => Err(map_inotify_error("poll", other)),
+    }
+}
+
+fn map_inotify_error(operation: &'static str, errno: Errno) -> Error {
+    let code = match errno {
+        Errno::EMFILE => ErrorCode::ProcessTooManyOpenFD,
+        Errno::ENFILE => ErrorCode::SystemTooManyOpenFiles,
+        Errno::ENOMEM => ErrorCode::OutOfMemory,
+        other => ErrorCode::Unknown(other),
+    };
+    Error::from_errno(code, operation, errno)
+}
+
+fn map_watch_error(errno: Errno) -> Error {
+    match errno {
+        Errno::EACCES => {
+            Error::from_errno(ErrorCode::ShmPathAccessDenied, "inotify_add_watch", errno)
+        }
+        Errno::ENOENT => {
+            Error::from_errno(ErrorCode::ShmPathDoesNotExist, "inotify_add_watch", errno)
+        }
+        Errno::ENOSPC => Error::from_errno(
+            ErrorCode::SystemTooManyOpenFiles,
+            "inotify_add_watch",
+            errno,
+        ),
+        other => map_inotify_error("inotify_add_watch", other),
+    }
+}